@@ -1,36 +1,114 @@
-use crate::Error;
+use crate::gitlab::RetryPolicy;
+use crate::testing::TestConfig;
+use crate::{files, Error};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 const CONFIG_FILE_NAME: &str = "forked.yml";
 
+/// The content of `forked.yml`.
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct Manifest {
-    pub projects: HashMap<String, Project>,
+    /// Id of the repository all projects are forked from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub root_project_id: Option<u32>,
+    /// Group whose projects are used instead of the forks of `RootProjectId`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<Group>,
+    /// Usernames that are never added as members (e.g. tutors)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude_members: Vec<String>,
+    /// Usernames by commit author name or email, for authors that don't match a member
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub aliases: BTreeMap<String, String>,
+    /// Projects by key, which is also the name of their clone
+    pub projects: BTreeMap<String, Project>,
+    /// Directory the projects are cloned into
     pub projects_directory: PathBuf,
+    /// Directory with the feedback templates and rubrics
     pub templates_directory: PathBuf,
+    /// Directory with one subdirectory of feedback files per feedback name
     pub feedbacks_directory: PathBuf,
+    /// How the tests are run, by feedback name
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub tests: BTreeMap<String, TestConfig>,
+    /// Retry policy for requests to gitlab, overridden by the command line
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry: Option<RetryPolicy>,
+    /// Custom keys, kept as they are
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, serde_yaml::Value>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// A gitlab group with one project per team.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Group {
+    /// Full path of the group, e.g. `course/ws20`
+    pub path: String,
+    /// Also include the projects of all subgroups
+    #[serde(default)]
+    pub include_subgroups: bool,
+    /// Regular expression the name of a project has to match
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filter: Option<String>,
+}
+
+/// A fork of the root repository (or a project of the group), worked on by one team.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct Project {
+    /// Id of the project on gitlab
     pub id: u32,
+    /// Members of the team, without `ExcludeMembers`
     pub members: Vec<Member>,
+    /// Ssh url the project is cloned from
     pub repository: String,
+    /// Url of the project on gitlab
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub web_url: Option<String>,
+    /// Custom keys, kept as they are
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, serde_yaml::Value>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// A student working on a project.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct Member {
+    /// Username on gitlab
     pub username: String,
+    /// Full name on gitlab
     pub name: String,
 }
 
 impl Manifest {
+    /// An empty course with the default directories.
+    pub fn new(root_project_id: Option<u32>) -> Self {
+        Manifest {
+            root_project_id,
+            group: None,
+            exclude_members: Vec::new(),
+            aliases: BTreeMap::new(),
+            projects: BTreeMap::new(),
+            projects_directory: "projects".into(),
+            templates_directory: "templates".into(),
+            feedbacks_directory: "feedbacks".into(),
+            tests: BTreeMap::new(),
+            retry: None,
+            extra: BTreeMap::new(),
+        }
+    }
+
+    /// Whether `forked.yml` exists in the current directory.
+    pub fn exists() -> bool {
+        Path::new(CONFIG_FILE_NAME).exists()
+    }
+
+    /// Loads and validates `forked.yml` from the current directory.
     pub fn load() -> anyhow::Result<Self> {
         let data = fs::read_to_string(CONFIG_FILE_NAME)?;
         let config: Self = serde_yaml::from_str(&data)?;
@@ -59,8 +137,12 @@ impl Manifest {
         Ok(config)
     }
 
-    pub fn save(self) -> anyhow::Result<()> {
-        fs::write(CONFIG_FILE_NAME, serde_yaml::to_string(&self)?)?;
-        Ok(())
+    /// Writes `forked.yml` to the current directory.
+    pub fn save(self, dry_run: bool) -> anyhow::Result<()> {
+        files::write(
+            Path::new(CONFIG_FILE_NAME),
+            &serde_yaml::to_string(&self)?,
+            dry_run,
+        )
     }
 }
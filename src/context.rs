@@ -0,0 +1,153 @@
+use crate::config::{Manifest, Project};
+use crate::git;
+use crate::grade::{self, Grade};
+use crate::testing::{self, TestRun};
+use chrono::{DateTime, FixedOffset};
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+
+/// Everything a feedback template can refer to.
+///
+/// The fields of the [`Project`] are available at the top level, as in `forked.yml`:
+///
+/// | Field        | Content                                                               |
+/// | ------------ | --------------------------------------------------------------------- |
+/// | `Key`        | key of the project in `forked.yml`, e.g. `team-a`                     |
+/// | `Name`       | name of the feedback, e.g. `sheet-1`                                  |
+/// | `Id`         | id of the project on gitlab                                           |
+/// | `Members`    | list of members with `Username` and `Name`                            |
+/// | `Repository` | ssh url of the repository                                             |
+/// | `WebUrl`     | url of the project on gitlab, if known                                |
+/// | `Commit`     | `Sha` and `Date` of the checked out commit, if the clone has commits  |
+/// | `Data`       | content of `<feedbacks>/<name>/data/<key>.yml` (or `.json`), if any   |
+/// | `Grade`      | `Criteria` with their `Points` and `Comments`, `Total` and `MaxTotal` |
+/// | `Tests`      | result of `forked test`, e.g. `ExitCode`, `Passed`, `Failed`, `Cases` |
+///
+/// Custom keys of the project in `forked.yml` are available at the top level as well.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Context {
+    /// Key of the project in `forked.yml`
+    pub key: String,
+    /// Name of the feedback
+    pub name: String,
+    /// The project as listed in `forked.yml`
+    #[serde(flatten)]
+    pub project: Project,
+    /// The checked out commit, `None` if the project is not cloned
+    pub commit: Option<Commit>,
+    /// Contents of the data file of the project, if it exists
+    pub data: Option<serde_json::Value>,
+    /// Scores of the project, if the feedback has a rubric
+    pub grade: Option<Grade>,
+    /// Test results of the project, if `forked test` was run for the feedback
+    pub tests: Option<TestRun>,
+}
+
+/// The checked out commit of a clone.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Commit {
+    /// Full sha of the commit
+    pub sha: String,
+    /// Committer date
+    pub date: DateTime<FixedOffset>,
+}
+
+impl Context {
+    /// Assembles the context of the feedback `name` for the project `key`.
+    pub async fn new(
+        config: &Manifest,
+        name: &str,
+        key: &str,
+        project: &Project,
+    ) -> anyhow::Result<Self> {
+        let dir = config.projects_directory.join(key);
+        let commit = if dir.exists() {
+            git::head(&dir)
+                .await?
+                .map(|(sha, date)| Commit { sha, date })
+        } else {
+            None
+        };
+
+        Ok(Context {
+            key: key.to_string(),
+            name: name.to_string(),
+            project: project.clone(),
+            commit,
+            data: load_data(&config.feedbacks_directory.join(name).join("data"), key)?,
+            grade: grade::grade(config, name, key)?,
+            tests: testing::load(&testing::results_path(
+                &config.feedbacks_directory,
+                name,
+                key,
+            ))?,
+        })
+    }
+}
+
+/// Loads `<key>.yml`, `<key>.yaml` or `<key>.json` from `directory`, if one of them exists.
+fn load_data(directory: &Path, key: &str) -> anyhow::Result<Option<serde_json::Value>> {
+    for extension in &["yml", "yaml", "json"] {
+        let path = directory.join(format!("{}.{}", key, extension));
+        if path.exists() {
+            // yaml is a superset of json
+            let data = serde_yaml::from_str(&fs::read_to_string(&path)?)
+                .map_err(|e| anyhow::anyhow!("{} is invalid: {}", path.display(), e))?;
+            return Ok(Some(data));
+        }
+    }
+
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Member;
+    use crate::git::test_utils::git;
+    use std::collections::BTreeMap;
+
+    #[tokio::test]
+    async fn context_contains_project_and_data() {
+        let directory = tempfile::tempdir().unwrap();
+        let mut config = Manifest::new(Some(1));
+        config.projects_directory = directory.path().join("projects");
+        config.templates_directory = directory.path().join("templates");
+        config.feedbacks_directory = directory.path().join("feedbacks");
+
+        let data = config.feedbacks_directory.join("sheet-1").join("data");
+        fs::create_dir_all(&data).unwrap();
+        fs::write(data.join("team-a.json"), r#"{"Points": 7}"#).unwrap();
+
+        let project = Project {
+            id: 10,
+            members: vec![Member {
+                username: "alice".into(),
+                name: "Alice".into(),
+            }],
+            repository: "git@example.com:team-a/exercise.git".into(),
+            web_url: Some("https://example.com/team-a/exercise".into()),
+            extra: BTreeMap::new(),
+        };
+
+        // a clone of a project without commits
+        let clone = config.projects_directory.join("team-a");
+        fs::create_dir_all(&clone).unwrap();
+        git(&clone, &["init"]);
+
+        let context = Context::new(&config, "sheet-1", "team-a", &project)
+            .await
+            .unwrap();
+        let value = serde_json::to_value(&context).unwrap();
+
+        assert_eq!(value["Key"], "team-a");
+        assert_eq!(value["Id"], 10);
+        assert_eq!(value["WebUrl"], "https://example.com/team-a/exercise");
+        assert_eq!(value["Members"][0]["Username"], "alice");
+        assert_eq!(value["Data"]["Points"], 7);
+        assert!(value["Commit"].is_null());
+    }
+}
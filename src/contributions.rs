@@ -0,0 +1,191 @@
+use crate::config::{Member, Project};
+use crate::git::{self, Change};
+use crate::late;
+use chrono::{DateTime, FixedOffset, NaiveDate};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::path::Path;
+
+/// Work of one author in a project.
+#[derive(Debug, Default, PartialEq, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Stats {
+    /// Commits without merges
+    pub commits: usize,
+    /// Added lines
+    pub added: usize,
+    /// Removed lines
+    pub removed: usize,
+    /// Days with at least one commit, by author date
+    pub active_days: BTreeSet<NaiveDate>,
+}
+
+/// Work of every member of a project and of authors that are not members.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Contributions {
+    /// Stats by username, including members without commits
+    pub members: BTreeMap<String, Stats>,
+    /// Stats by author name (or username from `Aliases`) of authors that aren't members, e.g.
+    /// tutors or the authors of the root repository
+    pub others: BTreeMap<String, Stats>,
+}
+
+impl Contributions {
+    /// Assigns every change to the member that authored it, see [`member_of`](crate::late::member_of).
+    pub fn new(members: &[Member], aliases: &BTreeMap<String, String>, changes: &[Change]) -> Self {
+        let mut contributions = Contributions {
+            members: members
+                .iter()
+                .map(|member| (member.username.clone(), Stats::default()))
+                .collect(),
+            others: BTreeMap::new(),
+        };
+
+        for change in changes {
+            let author = late::member_of(members, aliases, &change.commit);
+            let stats = match contributions.members.get_mut(&author) {
+                Some(stats) => stats,
+                None => contributions.others.entry(author).or_default(),
+            };
+            stats.commits += 1;
+            stats.added += change.added;
+            stats.removed += change.removed;
+            stats
+                .active_days
+                .insert(change.commit.date.date().naive_local());
+        }
+
+        contributions
+    }
+
+    /// Usernames of members without a single commit.
+    pub fn inactive(&self) -> Vec<&str> {
+        self.members
+            .iter()
+            .filter(|(_, stats)| stats.commits == 0)
+            .map(|(username, _)| username.as_str())
+            .collect()
+    }
+}
+
+/// Analyzes the history of the clone in `dir`, optionally only commits authored after `since`.
+pub async fn collect(
+    dir: &Path,
+    project: &Project,
+    aliases: &BTreeMap<String, String>,
+    since: Option<&DateTime<FixedOffset>>,
+) -> anyhow::Result<Contributions> {
+    if !dir.exists() {
+        return Err(anyhow::anyhow!("{} is not cloned", dir.display()));
+    }
+
+    let changes = git::changes(dir, since).await?;
+    Ok(Contributions::new(&project.members, aliases, &changes))
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} commit(s), +{} -{}, {} day(s)",
+            self.commits,
+            self.added,
+            self.removed,
+            self.active_days.len()
+        )
+    }
+}
+
+impl fmt::Display for Contributions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let inactive = self.inactive();
+        if !inactive.is_empty() {
+            write!(f, "inactive: {}; ", inactive.join(", "))?;
+        }
+
+        let parts: Vec<String> = self
+            .members
+            .iter()
+            .chain(&self.others)
+            .map(|(author, stats)| format!("{}: {}", author, stats))
+            .collect();
+        write!(f, "{}", parts.join("; "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::Commit;
+
+    #[test]
+    fn contributions_count_work_per_member() {
+        let members = vec![
+            Member {
+                username: "alice".into(),
+                name: "Alice Smith".into(),
+            },
+            Member {
+                username: "bob".into(),
+                name: "Bob".into(),
+            },
+            Member {
+                username: "carol".into(),
+                name: "Carol".into(),
+            },
+        ];
+        let aliases = vec![("laptop@localhost".to_string(), "bob".to_string())]
+            .into_iter()
+            .collect();
+        let change = |name: &str, email: &str, date: &str, added, removed| Change {
+            commit: Commit {
+                author_name: name.into(),
+                author_email: email.into(),
+                date: DateTime::parse_from_rfc3339(date).unwrap(),
+            },
+            added,
+            removed,
+        };
+        let changes = vec![
+            change(
+                "Alice Smith",
+                "a@example.com",
+                "2020-11-02T10:00:00+01:00",
+                10,
+                2,
+            ),
+            change("A.", "alice@example.com", "2020-11-02T18:00:00+01:00", 5, 0),
+            change(
+                "root",
+                "laptop@localhost",
+                "2020-11-03T09:00:00+01:00",
+                1,
+                1,
+            ),
+            change(
+                "Tutor",
+                "tutor@example.com",
+                "2020-10-01T09:00:00+01:00",
+                100,
+                0,
+            ),
+        ];
+
+        let contributions = Contributions::new(&members, &aliases, &changes);
+
+        assert_eq!(contributions.members["alice"].commits, 2);
+        assert_eq!(contributions.members["alice"].added, 15);
+        assert_eq!(contributions.members["alice"].active_days.len(), 1);
+        assert_eq!(contributions.members["bob"].removed, 1);
+        assert_eq!(contributions.inactive(), vec!["carol"]);
+        assert_eq!(
+            contributions.others.keys().collect::<Vec<_>>(),
+            vec!["Tutor"]
+        );
+        assert!(contributions.to_string().starts_with(
+            "inactive: carol; alice: 2 commit(s), +15 -2, 1 day(s); bob: 1 commit(s), +1 -1"
+        ));
+    }
+}
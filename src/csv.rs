@@ -0,0 +1,93 @@
+/// A line of csv, quoting cells that contain commas, quotes or line breaks.
+pub(crate) fn row(cells: &[String]) -> String {
+    let cells: Vec<String> = cells
+        .iter()
+        .map(|cell| {
+            if cell.contains(&[',', '"', '\n'][..]) {
+                format!("\"{}\"", cell.replace('"', "\"\""))
+            } else {
+                cell.clone()
+            }
+        })
+        .collect();
+
+    format!("{}\n", cells.join(","))
+}
+
+/// Splits a line of csv into its cells, the counterpart of [`row`].
+///
+/// Cells may be quoted to contain commas, with `""` for a quote inside a quoted cell. Quoted
+/// line breaks aren't supported, since the input is split into lines first.
+pub(crate) fn parse_row(line: &str) -> Result<Vec<String>, String> {
+    let mut cells = Vec::new();
+    let mut chars = line.chars().peekable();
+
+    loop {
+        let mut cell = String::new();
+        while chars.peek().is_some_and(|c| *c == ' ' || *c == '\t') {
+            chars.next();
+        }
+
+        if chars.peek() == Some(&'"') {
+            chars.next();
+            loop {
+                match chars.next() {
+                    Some('"') if chars.peek() == Some(&'"') => {
+                        chars.next();
+                        cell.push('"');
+                    }
+                    Some('"') => break,
+                    Some(c) => cell.push(c),
+                    None => return Err("a quoted cell is not closed".into()),
+                }
+            }
+            while chars.peek().is_some_and(|c| *c == ' ' || *c == '\t') {
+                chars.next();
+            }
+            match chars.next() {
+                Some(',') => cells.push(cell),
+                None => {
+                    cells.push(cell);
+                    return Ok(cells);
+                }
+                Some(c) => return Err(format!("unexpected `{}` after a quoted cell", c)),
+            }
+        } else {
+            loop {
+                match chars.next() {
+                    Some(',') => break,
+                    Some('"') => return Err("quotes must enclose the whole cell".into()),
+                    Some(c) => cell.push(c),
+                    None => {
+                        cells.push(cell.trim().to_string());
+                        return Ok(cells);
+                    }
+                }
+            }
+            cells.push(cell.trim().to_string());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_row_reads_what_row_writes() {
+        let cells = vec![
+            "Team A".to_string(),
+            "Smith, Jones".into(),
+            "say \"hi\"".into(),
+            String::new(),
+        ];
+        assert_eq!(parse_row(row(&cells).trim_end()).unwrap(), cells);
+        assert_eq!(
+            parse_row(" a , \"b\" ,c").unwrap(),
+            vec!["a".to_string(), "b".into(), "c".into()]
+        );
+        assert!(parse_row("\"open,alice").is_err());
+        assert!(parse_row("\"a\"b,alice").is_err());
+        assert!(parse_row("a\"b,alice").is_err());
+    }
+}
@@ -0,0 +1,186 @@
+use thiserror::Error;
+
+/// Errors caused by an invalid `forked.yml`, invalid arguments or unexpected responses.
+#[derive(Clone, Debug, Error)]
+pub enum Error {
+    /// A member in `forked.yml` has no username
+    #[error("`username` in project {} is empty", project_id)]
+    MissingUsername {
+        /// Id of the project
+        project_id: u32,
+    },
+    /// A project in `forked.yml` has no repository to clone
+    #[error("`repository` in project {} is empty", project_id)]
+    MissingSsh {
+        /// Id of the project
+        project_id: u32,
+    },
+    /// Neither `forked.yml` nor the command line say where the projects come from
+    #[error("`RootProjectId` and `Group` are missing in `forked.yml`, pass the id of the root repository")]
+    MissingRootProject,
+    /// A line of the roster can't be used
+    #[error("line {} of the roster is invalid ({})", line, message)]
+    InvalidRoster {
+        /// Line number, starting at 1
+        line: usize,
+        /// What is wrong with the line
+        message: String,
+    },
+    /// `test` was called for a feedback without a test command
+    #[error(
+        "no test command for {}, pass `--cmd` or add it to `Tests` in `forked.yml`",
+        name
+    )]
+    MissingTestCommand {
+        /// Name of the feedback
+        name: String,
+    },
+    /// A rubric or a score file is incomplete or out of range
+    #[error("`{}` {}", criterion, message)]
+    InvalidScore {
+        /// Id of the criterion
+        criterion: String,
+        /// What is wrong with the criterion or its score
+        message: String,
+    },
+    /// The filter of a group is not a valid regular expression
+    #[error("`{}` is not a valid filter ({})", filter, message)]
+    InvalidFilter {
+        /// The filter as given
+        filter: String,
+        /// Why it can't be parsed
+        message: String,
+    },
+    /// Two projects of a group would get the same key
+    #[error(
+        "projects {} and {} would both be stored as `{}` in `forked.yml`",
+        first,
+        second,
+        key
+    )]
+    DuplicateKey {
+        /// The shared key
+        key: String,
+        /// Id of the project found first
+        first: u32,
+        /// Id of the other project
+        second: u32,
+    },
+    /// The gitlab api url can't be parsed
+    #[error("`{}` is not a valid gitlab api url ({})", url, message)]
+    InvalidApiUrl {
+        /// The url as given
+        url: String,
+        /// Why it can't be parsed
+        message: String,
+    },
+    /// A command needs a newer version of git
+    #[error("{} needs git {} or newer, found {}", command, required, found)]
+    OutdatedGit {
+        /// The subcommand of `forked`
+        command: String,
+        /// The minimum version of git
+        required: String,
+        /// The installed version of git
+        found: String,
+    },
+    /// The command failed for some projects, whose errors were printed in the result table
+    #[error("the command failed for {} project(s)", failed)]
+    ProjectsFailed {
+        /// Number of failed projects
+        failed: usize,
+    },
+    /// A list endpoint has more pages than allowed by `--max-pages`
+    #[error("{} has more than {} pages, increase `--max-pages`", url, max_pages)]
+    TooManyPages {
+        /// Url of the first page
+        url: String,
+        /// The configured maximum
+        max_pages: u32,
+    },
+    /// Gitlab rejected the token
+    #[error("the gitlab token is invalid or expired ({})", message)]
+    Unauthorized {
+        /// Message of gitlab
+        message: String,
+    },
+    /// The token lacks a scope the request needs
+    #[error("the gitlab token lacks `{}` scope", scope)]
+    InsufficientScope {
+        /// The missing scope, e.g. `api`
+        scope: String,
+    },
+    /// The user of the token may not access a resource
+    #[error("access to {} is forbidden ({})", resource, message)]
+    Forbidden {
+        /// The requested resource, e.g. `project 1`
+        resource: String,
+        /// Message of gitlab
+        message: String,
+    },
+    /// A resource doesn't exist or isn't visible to the user of the token
+    #[error("{} not found", resource)]
+    NotFound {
+        /// The requested resource, e.g. `project 1`
+        resource: String,
+    },
+    /// Gitlab still rate limited a request after all retries
+    #[error("rate limit of gitlab exceeded while requesting {}", resource)]
+    RateLimited {
+        /// The requested resource, e.g. `project 1`
+        resource: String,
+    },
+    /// A response of gitlab can't be deserialized
+    #[error(
+        "unexpected response for {}: `{}` of {} is invalid ({})",
+        resource,
+        field,
+        item,
+        message
+    )]
+    InvalidResponse {
+        /// The requested resource, e.g. `forks of project 1`
+        resource: String,
+        /// The item of a list, e.g. `item 3`
+        item: String,
+        /// Path of the invalid field, e.g. `namespace.path`
+        field: String,
+        /// Why the field is invalid
+        message: String,
+    },
+    /// Any other unsuccessful response of gitlab
+    #[error("gitlab responded with {} for {} ({})", status, resource, message)]
+    Api {
+        /// Http status code
+        status: u16,
+        /// The requested resource, e.g. `project 1`
+        resource: String,
+        /// Message of gitlab
+        message: String,
+    },
+}
+
+impl Error {
+    /// The exit code of `forked` if it fails with this error.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::MissingUsername { .. }
+            | Error::MissingSsh { .. }
+            | Error::MissingRootProject
+            | Error::InvalidFilter { .. }
+            | Error::InvalidRoster { .. }
+            | Error::InvalidScore { .. }
+            | Error::MissingTestCommand { .. }
+            | Error::InvalidApiUrl { .. }
+            | Error::DuplicateKey { .. }
+            | Error::OutdatedGit { .. } => 1,
+            Error::Unauthorized { .. }
+            | Error::InsufficientScope { .. }
+            | Error::Forbidden { .. } => 3,
+            Error::NotFound { .. } => 4,
+            Error::RateLimited { .. } => 5,
+            Error::ProjectsFailed { .. } => 7,
+            Error::TooManyPages { .. } | Error::InvalidResponse { .. } | Error::Api { .. } => 6,
+        }
+    }
+}
@@ -0,0 +1,330 @@
+use crate::config::{Manifest, Project};
+use crate::context::Context;
+use crate::files;
+use crate::gitlab::GitlabApi;
+use crate::jobs::{Outcome, Runner};
+use crate::json::{EditIssueRequest, NewIssueRequest};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use tinytemplate::TinyTemplate;
+
+const PUBLISHED_FILE_NAME: &str = "published.yml";
+
+/// A feedback file, split into the title and the description of its issue.
+#[derive(Debug, PartialEq)]
+pub struct Feedback {
+    /// First line of the file
+    pub title: String,
+    /// All other lines
+    pub description: String,
+}
+
+impl Feedback {
+    /// Uses the first line (without leading `#`) as title and the rest as description.
+    pub fn parse(data: &str) -> Self {
+        let mut lines = data.lines();
+
+        let title = lines
+            .next()
+            .unwrap_or("Feedback")
+            .trim_matches('#')
+            .trim()
+            .to_string();
+
+        Feedback {
+            title,
+            description: lines.collect::<Vec<_>>().join("\n"),
+        }
+    }
+}
+
+/// Issues that were already published for one feedback, by project key.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Published {
+    /// Iid of the issue of every project
+    pub issues: BTreeMap<String, u32>,
+}
+
+impl Published {
+    /// Loads `published.yml` from the feedback directory, if it exists.
+    pub fn load(feedback_directory: &Path) -> anyhow::Result<Self> {
+        let path = feedback_directory.join(PUBLISHED_FILE_NAME);
+        if !path.exists() {
+            return Ok(Published::default());
+        }
+
+        Ok(serde_yaml::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    /// Remembers the issues of all successful publications.
+    pub fn record(&mut self, outcomes: &[Outcome<Publication>]) {
+        for outcome in outcomes {
+            if let Ok(publication) = &outcome.result {
+                self.issues.insert(outcome.key.clone(), publication.iid());
+            }
+        }
+    }
+
+    /// Writes `published.yml` to the feedback directory.
+    pub fn save(&self, feedback_directory: &Path, dry_run: bool) -> anyhow::Result<()> {
+        files::write(
+            &feedback_directory.join(PUBLISHED_FILE_NAME),
+            &serde_yaml::to_string(self)?,
+            dry_run,
+        )
+    }
+}
+
+/// What happened to the issue of a project while publishing.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Publication {
+    /// The issue with this iid was opened
+    Created(u32),
+    /// The issue with this iid was published before and has changed
+    Updated(u32),
+    /// The issue with this iid was published before and skipped, since it did not change
+    Unchanged(u32),
+}
+
+impl Publication {
+    /// The iid of the issue.
+    pub fn iid(self) -> u32 {
+        match self {
+            Publication::Created(iid) | Publication::Updated(iid) | Publication::Unchanged(iid) => {
+                iid
+            }
+        }
+    }
+}
+
+impl fmt::Display for Publication {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Publication::Created(iid) => write!(f, "created issue #{}", iid),
+            Publication::Updated(iid) => write!(f, "updated issue #{}", iid),
+            Publication::Unchanged(iid) => write!(f, "issue #{} is unchanged", iid),
+        }
+    }
+}
+
+/// Renders the template `name` with the [`Context`] of every project into one feedback file per
+/// project.
+///
+/// A project whose context can't be assembled, e.g. because of incomplete scores, fails on its
+/// own without stopping the others.
+pub async fn create(
+    config: &Manifest,
+    name: &str,
+    dry_run: bool,
+) -> anyhow::Result<Vec<Outcome<()>>> {
+    let mut tt = TinyTemplate::new();
+    let raw = fs::read_to_string(config.templates_directory.join(format!("{}.md", name)))?;
+    tt.add_template("Feedback", raw.as_str())?;
+
+    let feedback_directory = config.feedbacks_directory.join(name);
+    files::create_dir_all(&feedback_directory, dry_run)?;
+
+    let mut outcomes = Vec::new();
+    for (key, project) in &config.projects {
+        let result = match Context::new(config, name, key, project).await {
+            Ok(context) => tt
+                .render("Feedback", &context)
+                .map_err(Into::into)
+                .and_then(|rendered| {
+                    files::write(
+                        &feedback_directory.join(format!("{}.md", key)),
+                        &rendered,
+                        dry_run,
+                    )
+                }),
+            Err(e) => Err(e),
+        };
+
+        outcomes.push(Outcome {
+            key: key.clone(),
+            result,
+        });
+    }
+
+    Ok(outcomes)
+}
+
+/// Publishes the feedback of every project as an issue, updating issues in `published`.
+pub async fn publish(
+    api: &dyn GitlabApi,
+    runner: &Runner,
+    projects: BTreeMap<String, Project>,
+    feedback_directory: &Path,
+    published: &Published,
+    skip_unchanged: bool,
+) -> Vec<Outcome<Publication>> {
+    runner
+        .run(projects, |key, project| {
+            let feedback_path = feedback_directory.join(format!("{}.md", key));
+            let issue_iid = published.issues.get(&key).copied();
+
+            async move {
+                let Feedback { title, description } =
+                    Feedback::parse(&fs::read_to_string(feedback_path)?);
+
+                if let Some(iid) = issue_iid {
+                    if skip_unchanged {
+                        let issue = api.issue(project.id, iid).await?;
+                        if issue.title == title
+                            && issue.description.as_deref().unwrap_or_default() == description
+                        {
+                            return Ok(Publication::Unchanged(iid));
+                        }
+                    }
+
+                    api.update_issue(project.id, iid, &EditIssueRequest { title, description })
+                        .await?;
+                    return Ok(Publication::Updated(iid));
+                }
+
+                let request = NewIssueRequest {
+                    title,
+                    description,
+                    labels: vec!["feedback".into()],
+                };
+
+                let response = api.create_issue(project.id, &request).await?;
+
+                if response.is_opened() {
+                    Ok(Publication::Created(response.iid))
+                } else {
+                    Err(anyhow::anyhow!("issue was not opened"))
+                }
+            }
+        })
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gitlab::MockGitlab;
+    use crate::grade;
+    use crate::sync;
+    use tokio::time::Duration;
+
+    async fn setup() -> (
+        MockGitlab,
+        Runner,
+        BTreeMap<String, Project>,
+        tempfile::TempDir,
+    ) {
+        let api = MockGitlab::new().with_fork(1, 10, "team-a", &["alice"]);
+        let runner = Runner::new(4, Duration::from_millis(0));
+        let projects = sync::discover(&api, &runner, 1, &[]).await.unwrap();
+        let directory = tempfile::tempdir().unwrap();
+        fs::write(directory.path().join("team-a.md"), "# Sheet 1\nWell done").unwrap();
+
+        (api, runner, projects, directory)
+    }
+
+    #[tokio::test]
+    async fn create_fails_only_projects_with_incomplete_scores() {
+        let (_, _, projects, directory) = setup().await;
+        let mut config = Manifest::new(Some(1));
+        config.projects_directory = directory.path().join("projects");
+        config.templates_directory = directory.path().join("templates");
+        config.feedbacks_directory = directory.path().join("feedbacks");
+        config.projects = projects;
+        let mut team_b = config.projects["team-a"].clone();
+        team_b.id = 11;
+        config.projects.insert("team-b".into(), team_b);
+
+        fs::create_dir_all(&config.templates_directory).unwrap();
+        fs::write(
+            config.templates_directory.join("sheet-1.md"),
+            "# Sheet 1\n{Grade.Total} points",
+        )
+        .unwrap();
+        fs::write(
+            config.templates_directory.join("sheet-1.rubric.yml"),
+            "Criteria:\n  - {Id: tests, Title: Tests, MaxPoints: 10}\n",
+        )
+        .unwrap();
+        let scores = |key: &str, points: &str| {
+            let path = grade::scores_path(&config.feedbacks_directory, "sheet-1", key);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, format!("tests: {{Points: {}}}", points)).unwrap();
+        };
+        scores("team-a", "7");
+        scores("team-b", "null");
+
+        let outcomes = create(&config, "sheet-1", false).await.unwrap();
+
+        assert!(outcomes[0].result.is_ok());
+        assert_eq!(
+            fs::read_to_string(config.feedbacks_directory.join("sheet-1").join("team-a.md"))
+                .unwrap(),
+            "# Sheet 1\n7 points"
+        );
+        assert_eq!(
+            outcomes[1].result.as_ref().unwrap_err().to_string(),
+            "`tests` is not graded yet"
+        );
+    }
+
+    #[test]
+    fn parse_uses_first_line_as_title() {
+        assert_eq!(
+            Feedback::parse("## Sheet 1 ##\nline 1\nline 2"),
+            Feedback {
+                title: "Sheet 1".into(),
+                description: "line 1\nline 2".into(),
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn publish_updates_instead_of_duplicating() {
+        let (api, runner, projects, directory) = setup().await;
+        let mut published = Published::default();
+
+        let outcomes = publish(&api, &runner, projects, directory.path(), &published, false).await;
+        assert_eq!(
+            outcomes[0].result.as_ref().unwrap(),
+            &Publication::Created(1)
+        );
+        published.record(&outcomes);
+
+        fs::write(
+            directory.path().join("team-a.md"),
+            "# Sheet 1\nVery well done",
+        )
+        .unwrap();
+        let (_, _, projects, _) = setup().await;
+        let outcomes = publish(&api, &runner, projects, directory.path(), &published, false).await;
+        assert_eq!(
+            outcomes[0].result.as_ref().unwrap(),
+            &Publication::Updated(1)
+        );
+
+        let issues = api.issues(10);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].description.as_deref(), Some("Very well done"));
+    }
+
+    #[tokio::test]
+    async fn publish_skips_unchanged_issues() {
+        let (api, runner, projects, directory) = setup().await;
+        let mut published = Published::default();
+
+        let outcomes = publish(&api, &runner, projects, directory.path(), &published, true).await;
+        published.record(&outcomes);
+
+        let (_, _, projects, _) = setup().await;
+        let outcomes = publish(&api, &runner, projects, directory.path(), &published, true).await;
+        assert_eq!(
+            outcomes[0].result.as_ref().unwrap(),
+            &Publication::Unchanged(1)
+        );
+    }
+}
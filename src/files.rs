@@ -0,0 +1,22 @@
+use std::fs;
+use std::path::Path;
+
+/// Writes `contents` to `path`, or only prints them in dry-run mode.
+pub fn write(path: &Path, contents: &str, dry_run: bool) -> anyhow::Result<()> {
+    if dry_run {
+        println!("[dry-run] write {}\n{}", path.display(), contents);
+    } else {
+        fs::write(path, contents)?;
+    }
+    Ok(())
+}
+
+/// Creates `path` and all of its parents, or only prints it in dry-run mode.
+pub fn create_dir_all(path: &Path, dry_run: bool) -> anyhow::Result<()> {
+    if dry_run {
+        println!("[dry-run] create directory {}", path.display());
+    } else {
+        fs::create_dir_all(path)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,436 @@
+use chrono::{DateTime, FixedOffset};
+use std::path::Path;
+use std::process::Output;
+use tokio::process::Command;
+
+/// Runs `git <args>` inside `dir` and returns its stdout.
+pub async fn git(dir: &Path, args: &[&str]) -> anyhow::Result<String> {
+    let output = output(dir, args).await?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    } else {
+        Err(failed(args, &output))
+    }
+}
+
+async fn output(dir: &Path, args: &[&str]) -> anyhow::Result<Output> {
+    log::debug!("running `git {}` in {}", args.join(" "), dir.display());
+
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .await?;
+
+    log::debug!("{}", String::from_utf8_lossy(&output.stdout));
+    Ok(output)
+}
+
+/// Returns the major and minor version of the installed git.
+pub async fn version() -> anyhow::Result<(u32, u32)> {
+    let output = git(Path::new("."), &["--version"]).await?;
+    parse_version(&output).ok_or_else(|| anyhow::anyhow!("unknown git version `{}`", output.trim()))
+}
+
+/// Parses e.g. `git version 2.39.2` or `git version 2.37.1 (Apple Git-137.1)`.
+fn parse_version(output: &str) -> Option<(u32, u32)> {
+    let mut parts = output
+        .trim()
+        .strip_prefix("git version ")?
+        .split(|c: char| !c.is_ascii_digit());
+    Some((parts.next()?.parse().ok()?, parts.next()?.parse().ok()?))
+}
+
+fn failed(args: &[&str], output: &Output) -> anyhow::Error {
+    anyhow::anyhow!(
+        "`git {}` failed: {}",
+        args.first().unwrap_or(&""),
+        String::from_utf8_lossy(&output.stderr).trim()
+    )
+}
+
+/// Runs a `git` command that modifies the repository, or only prints it in dry-run mode.
+pub async fn run(dir: &Path, args: &[&str], dry_run: bool) -> anyhow::Result<String> {
+    if dry_run {
+        println!("[dry-run] git {} (in {})", args.join(" "), dir.display());
+        Ok(String::new())
+    } else {
+        git(dir, args).await
+    }
+}
+
+/// Clones `repository` into `directory/key`.
+pub async fn clone(
+    directory: &Path,
+    repository: &str,
+    key: &str,
+    dry_run: bool,
+) -> anyhow::Result<String> {
+    run(directory, &["clone", repository, key], dry_run).await
+}
+
+/// Runs `git pull` in the clone `dir`.
+pub async fn pull(dir: &Path, dry_run: bool) -> anyhow::Result<String> {
+    run(dir, &["pull"], dry_run).await
+}
+
+/// Checks out a branch or commit in the clone `dir`.
+pub async fn checkout(dir: &Path, reference: &str, dry_run: bool) -> anyhow::Result<String> {
+    run(dir, &["checkout", reference], dry_run).await
+}
+
+/// Returns the remote-tracking default branch of `origin`, e.g. `origin/master`.
+pub async fn default_branch(dir: &Path) -> anyhow::Result<String> {
+    Ok(git(dir, &["rev-parse", "--abbrev-ref", "origin/HEAD"])
+        .await?
+        .trim()
+        .to_string())
+}
+
+/// Whether `ancestor` is already contained in `reference`.
+pub async fn is_ancestor(dir: &Path, ancestor: &str, reference: &str) -> anyhow::Result<bool> {
+    let args = ["merge-base", "--is-ancestor", ancestor, reference];
+    let output = output(dir, &args).await?;
+
+    match output.status.code() {
+        Some(0) => Ok(true),
+        Some(1) => Ok(false),
+        _ => Err(failed(&args, &output)),
+    }
+}
+
+/// Returns the files that conflict when merging `other` into `base`, without touching the
+/// working tree.
+///
+/// Needs git 2.38 or newer for `git merge-tree --write-tree`.
+pub async fn merge_conflicts(dir: &Path, base: &str, other: &str) -> anyhow::Result<Vec<String>> {
+    let args = [
+        "merge-tree",
+        "--write-tree",
+        "--name-only",
+        "--no-messages",
+        base,
+        other,
+    ];
+    let output = output(dir, &args).await?;
+
+    match output.status.code() {
+        Some(0) => Ok(Vec::new()),
+        // the first line is the id of the merged tree, followed by the conflicting files
+        Some(1) => Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .skip(1)
+            .filter(|line| !line.is_empty())
+            .map(str::to_string)
+            .collect()),
+        _ => Err(failed(&args, &output)),
+    }
+}
+
+/// Returns the last commit on `branch` that was committed before `before`.
+pub async fn last_commit_before(
+    dir: &Path,
+    branch: &str,
+    before: &DateTime<FixedOffset>,
+) -> anyhow::Result<String> {
+    let sha = git(
+        dir,
+        &[
+            "rev-list",
+            "-1",
+            &format!("--before={}", before.to_rfc3339()),
+            branch,
+        ],
+    )
+    .await?
+    .trim()
+    .to_string();
+
+    if sha.is_empty() {
+        Err(anyhow::anyhow!("no commit on {} before {}", branch, before))
+    } else {
+        Ok(sha)
+    }
+}
+
+/// Returns the sha and the commit date of `HEAD`, `None` before the first commit.
+pub async fn head(dir: &Path) -> anyhow::Result<Option<(String, DateTime<FixedOffset>)>> {
+    let args = ["log", "-1", "--format=%H%x09%cI", "HEAD"];
+    let log = output(dir, &args).await?;
+    if !log.status.success() {
+        // `HEAD` points to a branch without commits, e.g. in a fresh clone of an empty project
+        let verify = ["rev-parse", "--verify", "--quiet", "HEAD"];
+        return match output(dir, &verify).await?.status.code() {
+            Some(1) => Ok(None),
+            _ => Err(failed(&args, &log)),
+        };
+    }
+
+    let log = String::from_utf8_lossy(&log.stdout);
+    match log.trim().split_once('\t') {
+        Some((sha, date)) => Ok(Some((sha.to_string(), DateTime::parse_from_rfc3339(date)?))),
+        None => Err(anyhow::anyhow!("unexpected `git log` output: {}", log)),
+    }
+}
+
+/// A commit as reported by `git log`.
+#[derive(Debug, Clone)]
+pub struct Commit {
+    /// Name of the author
+    pub author_name: String,
+    /// Email of the author
+    pub author_email: String,
+    /// Committer date for [`commits_since`], author date for [`changes`] and [`last_commit`]
+    pub date: DateTime<FixedOffset>,
+}
+
+/// Returns all commits on the remote branches of `origin` committed after `since`.
+pub async fn commits_since(
+    dir: &Path,
+    since: &DateTime<FixedOffset>,
+) -> anyhow::Result<Vec<Commit>> {
+    let log = git(
+        dir,
+        &[
+            "log",
+            "--remotes=origin",
+            &format!("--since={}", since.to_rfc3339()),
+            "--format=%an%x09%ae%x09%cI",
+        ],
+    )
+    .await?;
+
+    log.lines()
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            let fields: Vec<&str> = line.splitn(3, '\t').collect();
+            match fields.as_slice() {
+                [name, email, date] => Ok(Commit {
+                    author_name: name.to_string(),
+                    author_email: email.to_string(),
+                    date: DateTime::parse_from_rfc3339(date)?,
+                }),
+                _ => Err(anyhow::anyhow!("unexpected `git log` output: {}", line)),
+            }
+        })
+        .collect()
+}
+
+/// A commit with the number of lines it added and removed.
+#[derive(Debug, Clone)]
+pub struct Change {
+    /// The commit, with its author date
+    pub commit: Commit,
+    /// Added lines, binary files are not counted
+    pub added: usize,
+    /// Removed lines, binary files are not counted
+    pub removed: usize,
+}
+
+/// Returns all commits except merges on the local and remote branches, with the author date and
+/// the changed lines, optionally only those authored after `since`.
+///
+/// `since` is compared with the author date, unlike `git log --since`, which filters by the
+/// committer date that a rebase or an amend resets.
+pub async fn changes(
+    dir: &Path,
+    since: Option<&DateTime<FixedOffset>>,
+) -> anyhow::Result<Vec<Change>> {
+    let args = [
+        "log",
+        "--branches",
+        "--remotes",
+        "--no-merges",
+        "--numstat",
+        "--format=%x1e%an%x09%ae%x09%aI",
+    ];
+    let log = git(dir, &args).await?;
+
+    let changes: Vec<Change> = log
+        .split('\x1e')
+        .filter(|entry| !entry.trim().is_empty())
+        .map(|entry| {
+            let mut lines = entry.lines();
+            let header = lines.next().unwrap_or_default();
+            let commit = match header.splitn(3, '\t').collect::<Vec<_>>().as_slice() {
+                [name, email, date] => Commit {
+                    author_name: name.to_string(),
+                    author_email: email.to_string(),
+                    date: DateTime::parse_from_rfc3339(date)?,
+                },
+                _ => return Err(anyhow::anyhow!("unexpected `git log` output: {}", header)),
+            };
+
+            let mut change = Change {
+                commit,
+                added: 0,
+                removed: 0,
+            };
+            for line in lines.filter(|line| !line.is_empty()) {
+                // binary files are listed as `-	-	<path>`
+                let mut fields = line.split('\t');
+                change.added += fields.next().and_then(|n| n.parse().ok()).unwrap_or(0);
+                change.removed += fields.next().and_then(|n| n.parse().ok()).unwrap_or(0);
+            }
+            Ok(change)
+        })
+        .collect::<anyhow::Result<_>>()?;
+
+    Ok(changes
+        .into_iter()
+        .filter(|change| since.is_none_or(|since| change.commit.date > *since))
+        .collect())
+}
+
+/// The checked out branch and the state of the working tree of a clone.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorkingTree {
+    /// Sha of `HEAD`, `None` before the first commit
+    pub sha: Option<String>,
+    /// `None` if the head is detached
+    pub branch: Option<String>,
+    /// Commits ahead of and behind the upstream branch, `None` without upstream
+    pub ahead_behind: Option<(usize, usize)>,
+    /// Whether there are uncommitted or untracked changes
+    pub dirty: bool,
+}
+
+/// Reads the state of the clone `dir` from `git status`.
+pub async fn working_tree(dir: &Path) -> anyhow::Result<WorkingTree> {
+    let status = git(dir, &["status", "--porcelain=v2", "--branch"]).await?;
+
+    let mut tree = WorkingTree {
+        sha: None,
+        branch: None,
+        ahead_behind: None,
+        dirty: false,
+    };
+    for line in status.lines() {
+        match line.strip_prefix("# ") {
+            Some(header) => match header.split_once(' ') {
+                Some(("branch.oid", sha)) if sha != "(initial)" => tree.sha = Some(sha.into()),
+                Some(("branch.head", branch)) if branch != "(detached)" => {
+                    tree.branch = Some(branch.into())
+                }
+                Some(("branch.ab", counts)) => {
+                    let count = |prefix| {
+                        counts
+                            .split(' ')
+                            .find_map(|count: &str| count.strip_prefix(prefix)?.parse().ok())
+                            .unwrap_or(0)
+                    };
+                    tree.ahead_behind = Some((count("+"), count("-")));
+                }
+                _ => {}
+            },
+            None if !line.is_empty() => tree.dirty = true,
+            None => {}
+        }
+    }
+
+    Ok(tree)
+}
+
+/// Returns the author and the author date of `HEAD`.
+pub async fn last_commit(dir: &Path) -> anyhow::Result<Commit> {
+    let log = git(dir, &["log", "-1", "--format=%an%x09%ae%x09%aI", "HEAD"]).await?;
+
+    match log.trim().splitn(3, '\t').collect::<Vec<_>>().as_slice() {
+        [name, email, date] => Ok(Commit {
+            author_name: name.to_string(),
+            author_email: email.to_string(),
+            date: DateTime::parse_from_rfc3339(date)?,
+        }),
+        _ => Err(anyhow::anyhow!("unexpected `git log` output: {}", log)),
+    }
+}
+
+/// Helpers for tests that need real repositories.
+#[cfg(test)]
+pub(crate) mod test_utils {
+    use std::fs;
+    use std::path::Path;
+    use std::process::Command;
+
+    /// Runs `git <args>` in `dir` as Alice and fails the test if it fails.
+    pub fn git(dir: &Path, args: &[&str]) {
+        git_with_env(dir, args, &[]);
+    }
+
+    /// Like [`git`], with additional environment variables, e.g. `GIT_AUTHOR_DATE`.
+    pub fn git_with_env(dir: &Path, args: &[&str], env: &[(&str, &str)]) {
+        let status = Command::new("git")
+            .args([
+                "-c",
+                "user.name=Alice",
+                "-c",
+                "user.email=alice@example.com",
+            ])
+            .args(args)
+            .envs(env.iter().copied())
+            .current_dir(dir)
+            .output()
+            .unwrap()
+            .status;
+        assert!(status.success(), "git {:?} failed", args);
+    }
+
+    /// Writes `content` to `file` and commits it, with the name of the file as message.
+    pub fn commit(dir: &Path, file: &str, content: &str) {
+        fs::write(dir.join(file), content).unwrap();
+        git(dir, &["add", file]);
+        git(dir, &["commit", "-m", file]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_version_reads_major_and_minor() {
+        assert_eq!(parse_version("git version 2.39.2\n"), Some((2, 39)));
+        assert_eq!(
+            parse_version("git version 2.37.1 (Apple Git-137.1)"),
+            Some((2, 37))
+        );
+        assert_eq!(parse_version("git version 2.38.1.windows.1"), Some((2, 38)));
+        assert_eq!(parse_version("hub version 2.14.2"), None);
+    }
+
+    #[tokio::test]
+    async fn changes_since_compares_the_author_date() {
+        let repository = tempfile::tempdir().unwrap();
+        let dir = repository.path();
+        test_utils::git(dir, &["init", "-b", "master"]);
+        for (file, author_date) in [
+            ("old.rs", "2020-10-01T12:00:00+02:00"),
+            ("new.rs", "2020-11-02T12:00:00+01:00"),
+        ]
+        .iter()
+        {
+            std::fs::write(dir.join(file), "fn main() {}\n").unwrap();
+            test_utils::git(dir, &["add", file]);
+            // committed later, e.g. by a rebase
+            test_utils::git_with_env(
+                dir,
+                &["commit", "-m", file],
+                &[
+                    ("GIT_AUTHOR_DATE", author_date),
+                    ("GIT_COMMITTER_DATE", "2020-11-03T12:00:00+01:00"),
+                ],
+            );
+        }
+
+        let since = DateTime::parse_from_rfc3339("2020-11-01T00:00:00+01:00").unwrap();
+        let changes = changes(dir, Some(&since)).await.unwrap();
+
+        assert_eq!(changes.len(), 1);
+        assert_eq!(
+            changes[0].commit.date.to_rfc3339(),
+            "2020-11-02T12:00:00+01:00"
+        );
+        assert_eq!(changes[0].added, 1);
+    }
+}
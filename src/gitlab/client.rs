@@ -0,0 +1,690 @@
+use super::{GitlabApi, RetryPolicy};
+use crate::json::{
+    EditIssueRequest, EditMemberRequest, Event, Fork, Issue, Member, MergeRequest, NewForkRequest,
+    NewIssueRequest, NewIssueResponse, NewMemberRequest, NewMergeRequest, Version,
+};
+use crate::Error;
+use async_trait::async_trait;
+use chrono::NaiveDate;
+use reqwest::{
+    header::{HeaderMap, HeaderValue},
+    Client, RequestBuilder, Response, StatusCode, Url,
+};
+use serde::{de::DeserializeOwned, Deserialize};
+use tokio::time;
+
+/// How list endpoints are paginated.
+#[derive(Debug, Clone, Copy)]
+pub struct Pagination {
+    /// Number of items requested per page (gitlab allows at most 100)
+    pub per_page: u32,
+    /// Number of pages after which listing fails instead of silently truncating
+    pub max_pages: u32,
+}
+
+impl Default for Pagination {
+    fn default() -> Self {
+        Pagination {
+            per_page: 100,
+            max_pages: 100,
+        }
+    }
+}
+
+/// Talks to the REST api of a gitlab instance.
+pub struct Gitlab {
+    client: Client,
+    /// Normalized url ending in `/api/v4`
+    api_url: Url,
+    pagination: Pagination,
+    retry: RetryPolicy,
+}
+
+impl Gitlab {
+    /// `gitlab_api_url` may be given as `https://host`, `https://host/api` or `https://host/api/v4`.
+    pub fn new(gitlab_token: &str, gitlab_api_url: &str) -> anyhow::Result<Self> {
+        let mut headers = HeaderMap::new();
+        headers.insert("PRIVATE-TOKEN", HeaderValue::from_str(gitlab_token)?);
+
+        Ok(Gitlab {
+            client: Client::builder().default_headers(headers).build()?,
+            api_url: normalize_api_url(gitlab_api_url)?,
+            pagination: Pagination::default(),
+            retry: RetryPolicy::default(),
+        })
+    }
+
+    /// Sets how list endpoints are paginated.
+    pub fn with_pagination(mut self, pagination: Pagination) -> Self {
+        self.pagination = pagination;
+        self
+    }
+
+    /// Sets how failed requests are retried.
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Builds the url of an endpoint, e.g. `projects/1/forks`.
+    fn url(&self, path: &str) -> String {
+        format!("{}/{}", self.api_url, path)
+    }
+
+    /// Sends `request`, retrying it according to the [`RetryPolicy`], and turns unsuccessful
+    /// responses into an [`Error`] about `resource`.
+    ///
+    /// Requests that are not idempotent are only retried if they were rate limited or could not
+    /// connect, so that e.g. a fork is never created twice.
+    async fn send(&self, request: RequestBuilder, resource: &str) -> anyhow::Result<Response> {
+        let idempotent = request
+            .try_clone()
+            .and_then(|request| request.build().ok())
+            .is_some_and(|request| request.method().is_idempotent());
+        let mut attempt = 0;
+
+        loop {
+            let current = match request.try_clone() {
+                Some(current) if attempt < self.retry.max_retries => current,
+                _ => return check(request.send().await?, resource).await,
+            };
+
+            let delay = match current.send().await {
+                Ok(res) if RetryPolicy::is_retryable(res.status(), idempotent) => {
+                    log::warn!("{} responded with {}", resource, res.status());
+                    self.retry.delay(attempt, res.headers())
+                }
+                Ok(res) => return check(res, resource).await,
+                Err(e) if is_connect(&e) || idempotent && (e.is_timeout() || e.is_request()) => {
+                    log::warn!("requesting {} failed: {}", resource, e);
+                    self.retry.delay(attempt, &HeaderMap::new())
+                }
+                Err(e) => return Err(e.into()),
+            };
+
+            log::info!("retrying {} in {:?}", resource, delay);
+            time::delay_for(delay).await;
+            attempt += 1;
+        }
+    }
+
+    /// Requests all pages of a list endpoint, following the `Link` or `X-Next-Page` headers.
+    async fn get_all<T: DeserializeOwned>(
+        &self,
+        url: &str,
+        query: &[(&str, &str)],
+        resource: &str,
+    ) -> anyhow::Result<Vec<T>> {
+        let per_page = self.pagination.per_page.to_string();
+        let mut query: Vec<(&str, &str)> = query.to_vec();
+        query.push(("per_page", &per_page));
+        let mut next = Url::parse_with_params(url, &query)?;
+
+        let mut list = Vec::new();
+
+        for _ in 0..self.pagination.max_pages {
+            let res = self.send(self.client.get(next.clone()), resource).await?;
+            let following = next_page(&next, res.headers())?;
+
+            let data = res.text().await?.replace("\n", " ");
+            let current: Vec<serde_json::Value> = serde_json::from_str(&data)?;
+            for (index, value) in current.into_iter().enumerate() {
+                let item = match value.get("id") {
+                    Some(id) => format!("item {}", id),
+                    None => format!("item #{}", list.len() + index),
+                };
+                list.push(deserialize(value, resource, &item)?);
+            }
+
+            match following {
+                Some(url) => next = url,
+                None => return Ok(list),
+            }
+        }
+
+        Err(Error::TooManyPages {
+            url: url.to_string(),
+            max_pages: self.pagination.max_pages,
+        }
+        .into())
+    }
+}
+
+#[async_trait]
+impl GitlabApi for Gitlab {
+    async fn version(&self) -> anyhow::Result<Version> {
+        let request = self.client.get(&self.url("version"));
+
+        match self.send(request, "version").await {
+            Ok(res) => Ok(deserialize(res.json().await?, "version", "the response")?),
+            Err(e) => match e.downcast_ref::<Error>() {
+                Some(Error::NotFound { .. }) => Err(Error::InvalidApiUrl {
+                    url: self.api_url.to_string(),
+                    message: "`GET /version` was not found".into(),
+                }
+                .into()),
+                _ => Err(e),
+            },
+        }
+    }
+
+    async fn project(&self, project_id: u32) -> anyhow::Result<Fork> {
+        let request = self
+            .client
+            .get(&self.url(&format!("projects/{}", project_id)));
+        let resource = format!("project {}", project_id);
+
+        let res = self.send(request, &resource).await?;
+        Ok(deserialize(res.json().await?, &resource, "the project")?)
+    }
+
+    async fn forks(&self, project_id: u32) -> anyhow::Result<Vec<Fork>> {
+        self.get_all(
+            &self.url(&format!("projects/{}/forks", project_id)),
+            &[("order_by", "created_at"), ("sort", "asc")],
+            &format!("forks of project {}", project_id),
+        )
+        .await
+    }
+
+    async fn group_projects(
+        &self,
+        group: &str,
+        include_subgroups: bool,
+    ) -> anyhow::Result<Vec<Fork>> {
+        self.get_all(
+            &self.url(&format!("groups/{}/projects", group.replace('/', "%2F"))),
+            &[
+                ("include_subgroups", &include_subgroups.to_string()),
+                ("with_shared", "false"),
+                ("order_by", "path"),
+                ("sort", "asc"),
+            ],
+            &format!("projects of group {}", group),
+        )
+        .await
+    }
+
+    async fn members(&self, project_id: u32) -> anyhow::Result<Vec<Member>> {
+        self.get_all(
+            &self.url(&format!("projects/{}/members", project_id)),
+            &[],
+            &format!("members of project {}", project_id),
+        )
+        .await
+    }
+
+    async fn user(&self, username: &str) -> anyhow::Result<Member> {
+        let resource = format!("user {}", username);
+        let users: Vec<Member> = self
+            .get_all(&self.url("users"), &[("username", username)], &resource)
+            .await?;
+
+        users
+            .into_iter()
+            .next()
+            .ok_or_else(|| Error::NotFound { resource }.into())
+    }
+
+    async fn fork(&self, project_id: u32, fork: &NewForkRequest) -> anyhow::Result<Fork> {
+        let url = self.url(&format!("projects/{}/fork", project_id));
+
+        let request = self.client.post(&url).json(fork);
+        let resource = format!("project {}", project_id);
+
+        let res = self.send(request, &resource).await?;
+        Ok(deserialize(res.json().await?, &resource, "the fork")?)
+    }
+
+    async fn add_member(&self, project_id: u32, member: &NewMemberRequest) -> anyhow::Result<()> {
+        let url = self.url(&format!("projects/{}/members", project_id));
+
+        let request = self.client.post(&url).json(member);
+        let resource = format!("members of project {}", project_id);
+
+        self.send(request, &resource).await?;
+        Ok(())
+    }
+
+    async fn edit_member(
+        &self,
+        project_id: u32,
+        user_id: u32,
+        member: &EditMemberRequest,
+    ) -> anyhow::Result<()> {
+        let url = self.url(&format!("projects/{}/members/{}", project_id, user_id));
+
+        let request = self.client.put(&url).json(member);
+        let resource = format!("member {} of project {}", user_id, project_id);
+
+        self.send(request, &resource).await?;
+        Ok(())
+    }
+
+    async fn push_events(&self, project_id: u32, after: NaiveDate) -> anyhow::Result<Vec<Event>> {
+        self.get_all(
+            &self.url(&format!("projects/{}/events", project_id)),
+            &[("action", "pushed"), ("after", &after.to_string())],
+            &format!("events of project {}", project_id),
+        )
+        .await
+    }
+
+    async fn open_merge_requests(
+        &self,
+        project_id: u32,
+        source_branch: &str,
+    ) -> anyhow::Result<Vec<MergeRequest>> {
+        self.get_all(
+            &self.url(&format!("projects/{}/merge_requests", project_id)),
+            &[("state", "opened"), ("source_branch", source_branch)],
+            &format!("merge requests of project {}", project_id),
+        )
+        .await
+    }
+
+    async fn create_merge_request(
+        &self,
+        project_id: u32,
+        merge_request: &NewMergeRequest,
+    ) -> anyhow::Result<MergeRequest> {
+        let url = self.url(&format!("projects/{}/merge_requests", project_id));
+
+        let request = self.client.post(&url).json(merge_request);
+        let resource = format!("merge requests of project {}", project_id);
+
+        let res = self.send(request, &resource).await?;
+        Ok(deserialize(
+            res.json().await?,
+            &resource,
+            "the new merge request",
+        )?)
+    }
+
+    async fn issue(&self, project_id: u32, issue_iid: u32) -> anyhow::Result<Issue> {
+        let request = self
+            .client
+            .get(&self.url(&format!("projects/{}/issues/{}", project_id, issue_iid)));
+        let resource = format!("issue #{} of project {}", issue_iid, project_id);
+
+        let res = self.send(request, &resource).await?;
+        Ok(deserialize(res.json().await?, &resource, "the issue")?)
+    }
+
+    async fn create_issue(
+        &self,
+        project_id: u32,
+        issue: &NewIssueRequest,
+    ) -> anyhow::Result<NewIssueResponse> {
+        let url = self.url(&format!("projects/{}/issues", project_id));
+
+        let request = self.client.post(&url).json(issue);
+        let resource = format!("project {}", project_id);
+
+        let res = self.send(request, &resource).await?;
+        Ok(deserialize(res.json().await?, &resource, "the new issue")?)
+    }
+
+    async fn update_issue(
+        &self,
+        project_id: u32,
+        issue_iid: u32,
+        issue: &EditIssueRequest,
+    ) -> anyhow::Result<()> {
+        let url = self.url(&format!("projects/{}/issues/{}", project_id, issue_iid));
+
+        let request = self.client.put(&url).json(issue);
+        let resource = format!("issue #{} of project {}", issue_iid, project_id);
+
+        self.send(request, &resource).await?;
+        Ok(())
+    }
+}
+
+/// Normalizes `https://host`, `https://host/api` and `https://host/api/v4` to `https://host/api/v4`.
+fn normalize_api_url(gitlab_api_url: &str) -> Result<Url, Error> {
+    let invalid = |message: String| Error::InvalidApiUrl {
+        url: gitlab_api_url.to_string(),
+        message,
+    };
+
+    let url = Url::parse(gitlab_api_url).map_err(|e| invalid(e.to_string()))?;
+    if url.cannot_be_a_base() || !matches!(url.scheme(), "http" | "https") {
+        return Err(invalid("expected an http or https url".into()));
+    }
+
+    let path = url.path().trim_end_matches('/');
+    let path = if path.ends_with("/api/v4") {
+        path.to_string()
+    } else if path.ends_with("/api") {
+        format!("{}/v4", path)
+    } else {
+        format!("{}/api/v4", path)
+    };
+
+    let mut normalized = url.clone();
+    normalized.set_path(&path);
+    normalized.set_query(None);
+    normalized.set_fragment(None);
+    Ok(normalized)
+}
+
+/// Deserializes `value`, reporting the field that broke together with `resource` and `item`.
+fn deserialize<T: DeserializeOwned>(
+    value: serde_json::Value,
+    resource: &str,
+    item: &str,
+) -> Result<T, Error> {
+    serde_path_to_error::deserialize(value).map_err(|e| Error::InvalidResponse {
+        resource: resource.to_string(),
+        item: item.to_string(),
+        field: e.path().to_string(),
+        message: e.into_inner().to_string(),
+    })
+}
+
+/// Whether `error` occurred while connecting, i.e. before anything was sent.
+fn is_connect(error: &reqwest::Error) -> bool {
+    let mut source = std::error::Error::source(error);
+    while let Some(error) = source {
+        if let Some(error) = error.downcast_ref::<hyper::Error>() {
+            return error.is_connect();
+        }
+        source = error.source();
+    }
+    false
+}
+
+/// Turns an unsuccessful response for `resource` into an [`Error`].
+async fn check(res: Response, resource: &str) -> anyhow::Result<Response> {
+    let status = res.status();
+
+    if status.is_success() {
+        Ok(res)
+    } else {
+        let body = res.text().await.unwrap_or_default();
+        Err(api_error(status, resource, &body).into())
+    }
+}
+
+/// The body of an unsuccessful response, e.g. `{"message": "404 Project Not Found"}`.
+#[derive(Debug, Default, Deserialize)]
+struct ErrorResponse {
+    message: Option<serde_json::Value>,
+    error: Option<String>,
+    error_description: Option<String>,
+    scope: Option<String>,
+}
+
+/// Maps an unsuccessful response for `resource` to an [`Error`].
+fn api_error(status: StatusCode, resource: &str, body: &str) -> Error {
+    let response: ErrorResponse = serde_json::from_str(body).unwrap_or_default();
+
+    let message = match (&response.message, &response.error_description) {
+        (Some(serde_json::Value::String(message)), _) => message.clone(),
+        (Some(message), _) => message.to_string(),
+        (None, Some(description)) => description.clone(),
+        (None, None) => response
+            .error
+            .clone()
+            .unwrap_or_else(|| body.trim().to_string()),
+    };
+
+    match status {
+        StatusCode::UNAUTHORIZED => Error::Unauthorized { message },
+        StatusCode::FORBIDDEN if response.error.as_deref() == Some("insufficient_scope") => {
+            Error::InsufficientScope {
+                scope: response.scope.unwrap_or_else(|| "api".into()),
+            }
+        }
+        StatusCode::FORBIDDEN => Error::Forbidden {
+            resource: resource.to_string(),
+            message,
+        },
+        StatusCode::NOT_FOUND => Error::NotFound {
+            resource: resource.to_string(),
+        },
+        StatusCode::TOO_MANY_REQUESTS => Error::RateLimited {
+            resource: resource.to_string(),
+        },
+        _ => Error::Api {
+            status: status.as_u16(),
+            resource: resource.to_string(),
+            message,
+        },
+    }
+}
+
+/// Returns the url of the page after `current`, preferring the `Link` header over `X-Next-Page`.
+fn next_page(current: &Url, headers: &HeaderMap) -> anyhow::Result<Option<Url>> {
+    let link = headers
+        .get("link")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| {
+            value.split(',').find_map(|link| {
+                let mut parts = link.split(';');
+                let url = parts
+                    .next()?
+                    .trim()
+                    .trim_start_matches('<')
+                    .trim_end_matches('>');
+                parts
+                    .any(|part| part.trim() == "rel=\"next\"")
+                    .then(|| url.to_string())
+            })
+        });
+
+    if let Some(link) = link {
+        return Ok(Some(Url::parse(&link)?));
+    }
+
+    let page = headers
+        .get("x-next-page")
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+        .filter(|page| !page.is_empty());
+
+    Ok(page.map(|page| {
+        let mut next = current.clone();
+        let query: Vec<(String, String)> = current
+            .query_pairs()
+            .filter(|(key, _)| key != "page")
+            .map(|(key, value)| (key.into_owned(), value.into_owned()))
+            .collect();
+        next.query_pairs_mut()
+            .clear()
+            .extend_pairs(query)
+            .append_pair("page", page);
+        next
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+
+    fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (key, value) in pairs {
+            headers.insert(*key, HeaderValue::from_static(value));
+        }
+        headers
+    }
+
+    #[test]
+    fn deserialize_tolerates_missing_fields_and_names_broken_ones() {
+        let fork: Fork = deserialize(
+            serde_json::json!({
+                "id": 3,
+                "ssh_url_to_repo": "git@example.com:diaspora/diaspora-project-site.git",
+                "readme_url": null,
+                "shared_with_groups": [{"group_id": 4, "group_name": "staff"}],
+                "namespace": {"id": 3, "path": "diaspora"}
+            }),
+            "forks of project 1",
+            "item 3",
+        )
+        .unwrap();
+        assert_eq!(fork.namespace.path, "diaspora");
+        assert!(fork.web_url.is_none());
+
+        let error = deserialize::<Fork>(
+            serde_json::json!({
+                "id": 4,
+                "ssh_url_to_repo": "git@example.com:diaspora/diaspora-project-site.git",
+                "namespace": {"id": 3, "path": null}
+            }),
+            "forks of project 1",
+            "item 4",
+        )
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "unexpected response for forks of project 1: `namespace.path` of item 4 is invalid (invalid type: null, expected a string)"
+        );
+    }
+
+    #[test]
+    fn normalize_api_url_accepts_host_api_and_versioned_api() {
+        for url in &[
+            "https://gitlab.example.com",
+            "https://gitlab.example.com/",
+            "https://gitlab.example.com/api",
+            "https://gitlab.example.com/api/v4/",
+        ] {
+            assert_eq!(
+                normalize_api_url(url).unwrap().as_str(),
+                "https://gitlab.example.com/api/v4"
+            );
+        }
+
+        assert_eq!(
+            normalize_api_url("http://example.com/gitlab/api")
+                .unwrap()
+                .as_str(),
+            "http://example.com/gitlab/api/v4"
+        );
+        assert!(normalize_api_url("gitlab.example.com").is_err());
+    }
+
+    #[test]
+    fn api_error_uses_the_message_of_gitlab() {
+        let error = api_error(
+            StatusCode::NOT_FOUND,
+            "project 1234",
+            r#"{"message": "404 Project Not Found"}"#,
+        );
+        assert_eq!(error.to_string(), "project 1234 not found");
+        assert_eq!(error.exit_code(), 4);
+
+        let error = api_error(
+            StatusCode::FORBIDDEN,
+            "project 1234",
+            r#"{"error": "insufficient_scope", "error_description": "The request requires higher privileges than provided by the access token.", "scope": "api"}"#,
+        );
+        assert_eq!(error.to_string(), "the gitlab token lacks `api` scope");
+
+        let error = api_error(
+            StatusCode::BAD_REQUEST,
+            "project 1234",
+            r#"{"message": {"title": ["can't be blank"]}}"#,
+        );
+        assert_eq!(
+            error.to_string(),
+            r#"gitlab responded with 400 for project 1234 ({"title":["can't be blank"]})"#
+        );
+    }
+
+    #[test]
+    fn next_page_follows_the_link_header() {
+        let current = Url::parse("https://gitlab.com/api/v4/projects/1/forks?page=1").unwrap();
+        let next = next_page(
+            &current,
+            &headers(&[
+                ("link", "<https://gitlab.com/api/v4/projects/1/forks?page=2&per_page=100>; rel=\"next\", <https://gitlab.com/api/v4/projects/1/forks?page=1&per_page=100>; rel=\"first\""),
+                ("x-next-page", "3"),
+            ]),
+        )
+        .unwrap();
+
+        assert_eq!(
+            next.unwrap().as_str(),
+            "https://gitlab.com/api/v4/projects/1/forks?page=2&per_page=100"
+        );
+    }
+
+    #[test]
+    fn next_page_falls_back_to_x_next_page() {
+        let current =
+            Url::parse("https://gitlab.com/api/v4/projects/1/members?per_page=20&page=1").unwrap();
+        let next = next_page(&current, &headers(&[("x-next-page", "2")])).unwrap();
+
+        assert_eq!(
+            next.unwrap().as_str(),
+            "https://gitlab.com/api/v4/projects/1/members?per_page=20&page=2"
+        );
+    }
+
+    #[test]
+    fn next_page_is_none_on_the_last_page() {
+        let current = Url::parse("https://gitlab.com/api/v4/projects/1/members").unwrap();
+
+        assert!(next_page(&current, &headers(&[("x-next-page", "")]))
+            .unwrap()
+            .is_none());
+        assert!(next_page(&current, &HeaderMap::new()).unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn refused_connections_are_connect_errors() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        drop(listener);
+
+        let error = Client::new().post(&url).send().await.unwrap_err();
+
+        assert!(is_connect(&error));
+    }
+
+    /// Answers every request with 502 and counts them.
+    fn bad_gateway() -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        thread::spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                let mut buffer = [0; 4096];
+                let _ = stream.read(&mut buffer);
+                counter.fetch_add(1, Ordering::SeqCst);
+                let _ = stream.write_all(
+                    b"HTTP/1.1 502 Bad Gateway\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                );
+            }
+        });
+        (url, requests)
+    }
+
+    #[tokio::test]
+    async fn send_retries_gateway_errors_only_for_idempotent_requests() {
+        let (url, requests) = bad_gateway();
+        let gitlab = Gitlab::new("token", &url).unwrap().with_retry(RetryPolicy {
+            max_retries: 2,
+            base_delay: 1,
+            max_delay: 1,
+        });
+
+        let request = gitlab.client.post(&gitlab.url("projects/1/fork"));
+        assert!(gitlab.send(request, "fork of project 1").await.is_err());
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+        let request = gitlab.client.get(&gitlab.url("projects/1"));
+        assert!(gitlab.send(request, "project 1").await.is_err());
+        assert_eq!(requests.load(Ordering::SeqCst), 4);
+    }
+}
@@ -0,0 +1,205 @@
+use super::GitlabApi;
+use crate::json::{
+    EditIssueRequest, EditMemberRequest, Event, Fork, Issue, Member, MergeRequest, Namespace,
+    NewForkRequest, NewIssueRequest, NewIssueResponse, NewMemberRequest, NewMergeRequest, Version,
+};
+use async_trait::async_trait;
+use chrono::NaiveDate;
+use serde::Serialize;
+
+/// Passes requests that only read on to `api` and prints the requests that would change
+/// anything instead of sending them, for `--dry-run`.
+pub struct DryRun<A> {
+    api: A,
+}
+
+impl<A> DryRun<A> {
+    /// Wraps `api`, which only receives the requests that read.
+    pub fn new(api: A) -> Self {
+        DryRun { api }
+    }
+}
+
+fn print<T: Serialize>(method: &str, path: &str, body: &T) -> anyhow::Result<()> {
+    println!(
+        "[dry-run] {} {}\n{}",
+        method,
+        path,
+        serde_json::to_string_pretty(body)?
+    );
+    Ok(())
+}
+
+#[async_trait]
+impl<A: GitlabApi> GitlabApi for DryRun<A> {
+    async fn version(&self) -> anyhow::Result<Version> {
+        self.api.version().await
+    }
+
+    async fn project(&self, project_id: u32) -> anyhow::Result<Fork> {
+        self.api.project(project_id).await
+    }
+
+    async fn forks(&self, project_id: u32) -> anyhow::Result<Vec<Fork>> {
+        self.api.forks(project_id).await
+    }
+
+    async fn group_projects(
+        &self,
+        group: &str,
+        include_subgroups: bool,
+    ) -> anyhow::Result<Vec<Fork>> {
+        self.api.group_projects(group, include_subgroups).await
+    }
+
+    async fn members(&self, project_id: u32) -> anyhow::Result<Vec<Member>> {
+        self.api.members(project_id).await
+    }
+
+    async fn user(&self, username: &str) -> anyhow::Result<Member> {
+        self.api.user(username).await
+    }
+
+    async fn fork(&self, project_id: u32, fork: &NewForkRequest) -> anyhow::Result<Fork> {
+        print("POST", &format!("projects/{}/fork", project_id), fork)?;
+        Ok(Fork {
+            namespace: Namespace {
+                path: fork.path.clone(),
+                full_path: Some(fork.namespace_path.clone()),
+                ..Namespace::default()
+            },
+            ..Fork::default()
+        })
+    }
+
+    async fn add_member(&self, project_id: u32, member: &NewMemberRequest) -> anyhow::Result<()> {
+        print("POST", &format!("projects/{}/members", project_id), member)
+    }
+
+    async fn edit_member(
+        &self,
+        project_id: u32,
+        user_id: u32,
+        member: &EditMemberRequest,
+    ) -> anyhow::Result<()> {
+        let path = format!("projects/{}/members/{}", project_id, user_id);
+        print("PUT", &path, member)
+    }
+
+    async fn push_events(&self, project_id: u32, after: NaiveDate) -> anyhow::Result<Vec<Event>> {
+        self.api.push_events(project_id, after).await
+    }
+
+    async fn open_merge_requests(
+        &self,
+        project_id: u32,
+        source_branch: &str,
+    ) -> anyhow::Result<Vec<MergeRequest>> {
+        self.api
+            .open_merge_requests(project_id, source_branch)
+            .await
+    }
+
+    async fn create_merge_request(
+        &self,
+        project_id: u32,
+        merge_request: &NewMergeRequest,
+    ) -> anyhow::Result<MergeRequest> {
+        let path = format!("projects/{}/merge_requests", project_id);
+        print("POST", &path, merge_request)?;
+        Ok(MergeRequest {
+            iid: 0,
+            web_url: None,
+        })
+    }
+
+    async fn issue(&self, project_id: u32, issue_iid: u32) -> anyhow::Result<Issue> {
+        self.api.issue(project_id, issue_iid).await
+    }
+
+    async fn create_issue(
+        &self,
+        project_id: u32,
+        issue: &NewIssueRequest,
+    ) -> anyhow::Result<NewIssueResponse> {
+        print("POST", &format!("projects/{}/issues", project_id), issue)?;
+        Ok(NewIssueResponse {
+            iid: 0,
+            state: "opened".into(),
+        })
+    }
+
+    async fn update_issue(
+        &self,
+        project_id: u32,
+        issue_iid: u32,
+        issue: &EditIssueRequest,
+    ) -> anyhow::Result<()> {
+        let path = format!("projects/{}/issues/{}", project_id, issue_iid);
+        print("PUT", &path, issue)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::feedback::{self, Published};
+    use crate::gitlab::MockGitlab;
+    use crate::jobs::Runner;
+    use crate::json::AccessLevel;
+    use crate::lock::{self, Locked};
+    use crate::{provision, sync};
+    use std::collections::BTreeMap;
+    use std::fs;
+    use tokio::time::Duration;
+
+    #[tokio::test]
+    async fn dry_run_sends_no_request_that_changes_anything() {
+        let api = DryRun::new(
+            MockGitlab::new()
+                .with_fork(1, 10, "team-a", &["alice"])
+                .with_user("bob"),
+        );
+        let runner = Runner::new(4, Duration::from_millis(0));
+        let projects = sync::discover(&api, &runner, 1, &[]).await.unwrap();
+
+        let teams = provision::parse_roster("team-b,bob").unwrap();
+        let outcomes = provision::provision(
+            &api,
+            &runner,
+            1,
+            "course",
+            teams,
+            &BTreeMap::new(),
+            AccessLevel::Developer,
+        )
+        .await;
+        assert!(outcomes[0].result.is_ok());
+
+        let outcomes = lock::lock(
+            &api,
+            &runner,
+            projects.clone(),
+            &Locked::default(),
+            AccessLevel::Reporter,
+        )
+        .await;
+        assert_eq!(outcomes[0].result.as_ref().unwrap().members.len(), 1);
+
+        let feedbacks = tempfile::tempdir().unwrap();
+        fs::write(feedbacks.path().join("team-a.md"), "# Sheet 1").unwrap();
+        let outcomes = feedback::publish(
+            &api,
+            &runner,
+            projects,
+            feedbacks.path(),
+            &Published::default(),
+            false,
+        )
+        .await;
+        assert!(outcomes[0].result.is_ok());
+
+        assert_eq!(api.api.mutations(), 0);
+        assert_eq!(api.api.forks_of(1).len(), 1);
+    }
+}
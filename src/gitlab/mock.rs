@@ -0,0 +1,436 @@
+use super::GitlabApi;
+use crate::json::{
+    AccessLevel, EditIssueRequest, EditMemberRequest, Event, Fork, Issue, Member, MergeRequest,
+    Namespace, NewForkRequest, NewIssueRequest, NewIssueResponse, NewMemberRequest,
+    NewMergeRequest, Version,
+};
+use crate::Error;
+use async_trait::async_trait;
+use chrono::NaiveDate;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+/// An in-memory gitlab instance, e.g. for tests.
+#[derive(Debug, Default)]
+pub struct MockGitlab {
+    forks: Mutex<BTreeMap<u32, Vec<Fork>>>,
+    /// Projects by the full path of their group, including projects shared with the group
+    groups: BTreeMap<String, Vec<Fork>>,
+    members: Mutex<BTreeMap<u32, Vec<Member>>>,
+    /// User ids by username, users are registered by adding them as members or with `with_user`
+    users: BTreeMap<String, u32>,
+    events: BTreeMap<u32, Vec<Event>>,
+    /// Merge requests by project id, the iid of a merge request is its index plus one
+    merge_requests: Mutex<BTreeMap<u32, Vec<NewMergeRequest>>>,
+    /// Issues by project id, the iid of an issue is its index plus one
+    issues: Mutex<BTreeMap<u32, Vec<Issue>>>,
+    /// User ids whose next `add_member` or `edit_member` fails
+    failing_members: Mutex<BTreeSet<u32>>,
+    /// Number of requests that change anything, including failed ones
+    mutations: AtomicUsize,
+}
+
+impl MockGitlab {
+    /// An instance without projects and users.
+    pub fn new() -> Self {
+        MockGitlab::default()
+    }
+
+    /// Adds a fork of `root_project_id` in the namespace `namespace` with the given members.
+    pub fn with_fork(
+        mut self,
+        root_project_id: u32,
+        fork_id: u32,
+        namespace: &str,
+        usernames: &[&str],
+    ) -> Self {
+        self.forks
+            .get_mut()
+            .unwrap()
+            .entry(root_project_id)
+            .or_default()
+            .push(project(fork_id, namespace, "exercise"));
+        self.with_members(fork_id, usernames)
+    }
+
+    /// Adds the project `path` with the given members to the group (or subgroup) `group`.
+    pub fn with_group_project(
+        mut self,
+        group: &str,
+        project_id: u32,
+        path: &str,
+        usernames: &[&str],
+    ) -> Self {
+        self.groups
+            .entry(group.to_string())
+            .or_default()
+            .push(project(project_id, group, path));
+        self.with_members(project_id, usernames)
+    }
+
+    /// Adds the project `path` of `namespace` with the given members and shares it with `group`.
+    ///
+    /// Like gitlab with `with_shared=false`, `group_projects` does not list shared projects.
+    pub fn with_shared_project(
+        mut self,
+        group: &str,
+        project_id: u32,
+        namespace: &str,
+        path: &str,
+        usernames: &[&str],
+    ) -> Self {
+        self.groups
+            .entry(group.to_string())
+            .or_default()
+            .push(project(project_id, namespace, path));
+        self.with_members(project_id, usernames)
+    }
+
+    /// Registers a user that is not a member of any project yet.
+    pub fn with_user(mut self, username: &str) -> Self {
+        self.user_id(username);
+        self
+    }
+
+    fn with_members(mut self, project_id: u32, usernames: &[&str]) -> Self {
+        let members = usernames
+            .iter()
+            .map(|username| Member {
+                id: self.user_id(username),
+                username: username.to_string(),
+                name: username.to_string(),
+                access_level: Some(AccessLevel::Developer),
+            })
+            .collect();
+        self.members.get_mut().unwrap().insert(project_id, members);
+        self
+    }
+
+    /// Makes `username` the owner of the project, whose access level can't be changed.
+    pub fn with_owner(mut self, project_id: u32, username: &str) -> Self {
+        let id = self.user_id(username);
+        let members = self
+            .members
+            .get_mut()
+            .unwrap()
+            .entry(project_id)
+            .or_default();
+        match members.iter_mut().find(|member| member.id == id) {
+            Some(member) => member.access_level = Some(AccessLevel::Owner),
+            None => members.push(Member {
+                id,
+                username: username.to_string(),
+                name: username.to_string(),
+                access_level: Some(AccessLevel::Owner),
+            }),
+        }
+        self
+    }
+
+    /// Lets the next addition of `username` as a member or change of its access level fail.
+    pub fn with_failing_member(mut self, username: &str) -> Self {
+        let id = self.user_id(username);
+        self.failing_members.get_mut().unwrap().insert(id);
+        self
+    }
+
+    fn user_id(&mut self, username: &str) -> u32 {
+        let next = self.users.len() as u32 + 1;
+        *self.users.entry(username.to_string()).or_insert(next)
+    }
+
+    /// Adds an event, e.g. a push, to the project `project_id`.
+    pub fn with_event(mut self, project_id: u32, event: Event) -> Self {
+        self.events.entry(project_id).or_default().push(event);
+        self
+    }
+
+    /// Returns all forks of a project, including the ones created with [`GitlabApi::fork`].
+    pub fn forks_of(&self, project_id: u32) -> Vec<Fork> {
+        self.forks
+            .lock()
+            .unwrap()
+            .get(&project_id)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Returns the number of requests that change anything, e.g. forks or issues.
+    pub fn mutations(&self) -> usize {
+        self.mutations.load(Ordering::SeqCst)
+    }
+
+    /// Returns all merge requests of a project, ordered by iid.
+    pub fn merge_requests(&self, project_id: u32) -> Vec<NewMergeRequest> {
+        self.merge_requests
+            .lock()
+            .unwrap()
+            .get(&project_id)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Returns all issues of a project, ordered by iid.
+    pub fn issues(&self, project_id: u32) -> Vec<Issue> {
+        self.issues
+            .lock()
+            .unwrap()
+            .get(&project_id)
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+fn project(id: u32, namespace: &str, path: &str) -> Fork {
+    Fork {
+        id,
+        ssh_url_to_repo: format!("git@example.com:{}/{}.git", namespace, path),
+        web_url: Some(format!("https://example.com/{}/{}", namespace, path)),
+        name: Some(path.to_string()),
+        path: Some(path.to_string()),
+        path_with_namespace: Some(format!("{}/{}", namespace, path)),
+        namespace: Namespace {
+            path: namespace.rsplit('/').next().unwrap_or_default().to_string(),
+            full_path: Some(namespace.to_string()),
+            ..Namespace::default()
+        },
+        ..Fork::default()
+    }
+}
+
+#[async_trait]
+impl GitlabApi for MockGitlab {
+    async fn version(&self) -> anyhow::Result<Version> {
+        Ok(Version {
+            version: "13.6.0".into(),
+            revision: "mock".into(),
+        })
+    }
+
+    async fn project(&self, project_id: u32) -> anyhow::Result<Fork> {
+        let forks = self.forks.lock().unwrap();
+        forks
+            .values()
+            .chain(self.groups.values())
+            .flatten()
+            .find(|project| project.id == project_id)
+            .cloned()
+            .ok_or_else(|| {
+                Error::NotFound {
+                    resource: format!("project {}", project_id),
+                }
+                .into()
+            })
+    }
+
+    async fn forks(&self, project_id: u32) -> anyhow::Result<Vec<Fork>> {
+        Ok(self.forks_of(project_id))
+    }
+
+    async fn group_projects(
+        &self,
+        group: &str,
+        include_subgroups: bool,
+    ) -> anyhow::Result<Vec<Fork>> {
+        let subgroups = format!("{}/", group);
+        Ok(self
+            .groups
+            .iter()
+            .filter(|(path, _)| {
+                *path == group || (include_subgroups && path.starts_with(&subgroups))
+            })
+            .flat_map(|(path, projects)| {
+                projects
+                    .iter()
+                    .filter(move |project| project.namespace.full_path.as_ref() == Some(path))
+                    .cloned()
+            })
+            .collect())
+    }
+
+    async fn members(&self, project_id: u32) -> anyhow::Result<Vec<Member>> {
+        self.members
+            .lock()
+            .unwrap()
+            .get(&project_id)
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("project {} not found", project_id))
+    }
+
+    async fn user(&self, username: &str) -> anyhow::Result<Member> {
+        let id = self.users.get(username).ok_or_else(|| Error::NotFound {
+            resource: format!("user {}", username),
+        })?;
+
+        Ok(Member {
+            id: *id,
+            username: username.to_string(),
+            name: username.to_string(),
+            access_level: None,
+        })
+    }
+
+    async fn fork(&self, project_id: u32, fork: &NewForkRequest) -> anyhow::Result<Fork> {
+        self.mutations.fetch_add(1, Ordering::SeqCst);
+        let mut members = self.members.lock().unwrap();
+        let id = members.keys().max().copied().unwrap_or(project_id) + 1;
+        let created = project(id, &fork.namespace_path, &fork.path);
+
+        members.insert(id, Vec::new());
+        self.forks
+            .lock()
+            .unwrap()
+            .entry(project_id)
+            .or_default()
+            .push(created.clone());
+        Ok(created)
+    }
+
+    async fn add_member(&self, project_id: u32, member: &NewMemberRequest) -> anyhow::Result<()> {
+        self.mutations.fetch_add(1, Ordering::SeqCst);
+        if self.failing_members.lock().unwrap().remove(&member.user_id) {
+            return Err(anyhow::anyhow!("adding member {} failed", member.user_id));
+        }
+
+        let (username, _) = self
+            .users
+            .iter()
+            .find(|(_, id)| **id == member.user_id)
+            .ok_or_else(|| anyhow::anyhow!("user {} not found", member.user_id))?;
+
+        self.members
+            .lock()
+            .unwrap()
+            .get_mut(&project_id)
+            .ok_or_else(|| anyhow::anyhow!("project {} not found", project_id))?
+            .push(Member {
+                id: member.user_id,
+                username: username.clone(),
+                name: username.clone(),
+                access_level: Some(member.access_level),
+            });
+        Ok(())
+    }
+
+    async fn edit_member(
+        &self,
+        project_id: u32,
+        user_id: u32,
+        member: &EditMemberRequest,
+    ) -> anyhow::Result<()> {
+        self.mutations.fetch_add(1, Ordering::SeqCst);
+        if self.failing_members.lock().unwrap().remove(&user_id) {
+            return Err(anyhow::anyhow!("editing member {} failed", user_id));
+        }
+
+        let mut members = self.members.lock().unwrap();
+        let existing = members
+            .get_mut(&project_id)
+            .and_then(|members| members.iter_mut().find(|existing| existing.id == user_id))
+            .ok_or_else(|| {
+                anyhow::anyhow!("member {} of project {} not found", user_id, project_id)
+            })?;
+        if existing.access_level == Some(AccessLevel::Owner) {
+            return Err(anyhow::anyhow!(
+                "the access level of the owner can't be changed"
+            ));
+        }
+
+        existing.access_level = Some(member.access_level);
+        Ok(())
+    }
+
+    async fn push_events(&self, project_id: u32, after: NaiveDate) -> anyhow::Result<Vec<Event>> {
+        Ok(self
+            .events
+            .get(&project_id)
+            .into_iter()
+            .flatten()
+            .filter(|event| event.created_at.date().naive_utc() > after)
+            .cloned()
+            .collect())
+    }
+
+    async fn open_merge_requests(
+        &self,
+        project_id: u32,
+        source_branch: &str,
+    ) -> anyhow::Result<Vec<MergeRequest>> {
+        Ok(self
+            .merge_requests(project_id)
+            .iter()
+            .enumerate()
+            .filter(|(_, merge_request)| merge_request.source_branch == source_branch)
+            .map(|(index, _)| MergeRequest {
+                iid: index as u32 + 1,
+                web_url: None,
+            })
+            .collect())
+    }
+
+    async fn create_merge_request(
+        &self,
+        project_id: u32,
+        merge_request: &NewMergeRequest,
+    ) -> anyhow::Result<MergeRequest> {
+        self.mutations.fetch_add(1, Ordering::SeqCst);
+        let mut merge_requests = self.merge_requests.lock().unwrap();
+        let merge_requests = merge_requests.entry(project_id).or_default();
+        merge_requests.push(merge_request.clone());
+
+        Ok(MergeRequest {
+            iid: merge_requests.len() as u32,
+            web_url: None,
+        })
+    }
+
+    async fn issue(&self, project_id: u32, issue_iid: u32) -> anyhow::Result<Issue> {
+        (issue_iid as usize)
+            .checked_sub(1)
+            .and_then(|index| self.issues(project_id).get(index).cloned())
+            .ok_or_else(|| {
+                anyhow::anyhow!("issue {} of project {} not found", issue_iid, project_id)
+            })
+    }
+
+    async fn create_issue(
+        &self,
+        project_id: u32,
+        issue: &NewIssueRequest,
+    ) -> anyhow::Result<NewIssueResponse> {
+        self.mutations.fetch_add(1, Ordering::SeqCst);
+        let mut issues = self.issues.lock().unwrap();
+        let issues = issues.entry(project_id).or_default();
+        issues.push(Issue {
+            title: issue.title.clone(),
+            description: Some(issue.description.clone()),
+        });
+
+        Ok(NewIssueResponse {
+            iid: issues.len() as u32,
+            state: "opened".into(),
+        })
+    }
+
+    async fn update_issue(
+        &self,
+        project_id: u32,
+        issue_iid: u32,
+        issue: &EditIssueRequest,
+    ) -> anyhow::Result<()> {
+        self.mutations.fetch_add(1, Ordering::SeqCst);
+        let mut issues = self.issues.lock().unwrap();
+        let existing = issues
+            .get_mut(&project_id)
+            .zip((issue_iid as usize).checked_sub(1))
+            .and_then(|(issues, index)| issues.get_mut(index))
+            .ok_or_else(|| {
+                anyhow::anyhow!("issue {} of project {} not found", issue_iid, project_id)
+            })?;
+
+        existing.title = issue.title.clone();
+        existing.description = Some(issue.description.clone());
+        Ok(())
+    }
+}
@@ -0,0 +1,94 @@
+use crate::json::{
+    EditIssueRequest, EditMemberRequest, Event, Fork, Issue, Member, MergeRequest, NewForkRequest,
+    NewIssueRequest, NewIssueResponse, NewMemberRequest, NewMergeRequest, Version,
+};
+use async_trait::async_trait;
+use chrono::NaiveDate;
+
+mod client;
+pub use client::{Gitlab, Pagination};
+
+mod dry_run;
+pub use dry_run::DryRun;
+
+mod mock;
+pub use mock::MockGitlab;
+
+mod retry;
+pub use retry::RetryPolicy;
+
+/// All requests `forked` sends to gitlab.
+#[async_trait]
+pub trait GitlabApi: Send + Sync {
+    /// Returns the version of gitlab, which also checks the url and the token.
+    async fn version(&self) -> anyhow::Result<Version>;
+
+    /// Returns a single project.
+    async fn project(&self, project_id: u32) -> anyhow::Result<Fork>;
+
+    /// Lists all forks of a project.
+    async fn forks(&self, project_id: u32) -> anyhow::Result<Vec<Fork>>;
+
+    /// Lists all projects of a group, including the projects of its subgroups if
+    /// `include_subgroups` is set.
+    async fn group_projects(
+        &self,
+        group: &str,
+        include_subgroups: bool,
+    ) -> anyhow::Result<Vec<Fork>>;
+
+    /// Lists the members of a project.
+    async fn members(&self, project_id: u32) -> anyhow::Result<Vec<Member>>;
+
+    /// Looks up a user by username.
+    async fn user(&self, username: &str) -> anyhow::Result<Member>;
+
+    /// Forks a project into another namespace.
+    async fn fork(&self, project_id: u32, fork: &NewForkRequest) -> anyhow::Result<Fork>;
+
+    /// Adds a user as member of a project.
+    async fn add_member(&self, project_id: u32, member: &NewMemberRequest) -> anyhow::Result<()>;
+
+    /// Changes the access level of a member of a project.
+    async fn edit_member(
+        &self,
+        project_id: u32,
+        user_id: u32,
+        member: &EditMemberRequest,
+    ) -> anyhow::Result<()>;
+
+    /// Lists all push events of a project that happened after the day `after`.
+    async fn push_events(&self, project_id: u32, after: NaiveDate) -> anyhow::Result<Vec<Event>>;
+
+    /// Lists the open merge requests of a project from `source_branch`.
+    async fn open_merge_requests(
+        &self,
+        project_id: u32,
+        source_branch: &str,
+    ) -> anyhow::Result<Vec<MergeRequest>>;
+
+    /// Opens a merge request within a project.
+    async fn create_merge_request(
+        &self,
+        project_id: u32,
+        merge_request: &NewMergeRequest,
+    ) -> anyhow::Result<MergeRequest>;
+
+    /// Looks up an issue of a project by its iid.
+    async fn issue(&self, project_id: u32, issue_iid: u32) -> anyhow::Result<Issue>;
+
+    /// Opens an issue in a project.
+    async fn create_issue(
+        &self,
+        project_id: u32,
+        issue: &NewIssueRequest,
+    ) -> anyhow::Result<NewIssueResponse>;
+
+    /// Replaces the title and the description of an issue.
+    async fn update_issue(
+        &self,
+        project_id: u32,
+        issue_iid: u32,
+        issue: &EditIssueRequest,
+    ) -> anyhow::Result<()>;
+}
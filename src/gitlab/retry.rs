@@ -0,0 +1,144 @@
+use chrono::{DateTime, Utc};
+use rand::Rng;
+use reqwest::{header::HeaderMap, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// How often and how long to wait before a failed request is sent again.
+///
+/// Can be set in `forked.yml`:
+///
+/// ``` yaml
+/// Retry:
+///   MaxRetries: 5
+///   BaseDelay: 1000
+///   MaxDelay: 60000
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase", default)]
+pub struct RetryPolicy {
+    /// Number of retries after the first attempt
+    pub max_retries: u32,
+    /// Delay before the first retry in milliseconds, doubled for every further retry
+    pub base_delay: u64,
+    /// Upper bound of the exponential delay in milliseconds
+    pub max_delay: u64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_retries: 5,
+            base_delay: 1000,
+            max_delay: 60_000,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Whether a response with this status is worth sending again.
+    ///
+    /// Requests that are not idempotent, e.g. `POST`, are only retried on rate limits, since a
+    /// gateway error doesn't tell whether gitlab already performed them.
+    pub fn is_retryable(status: StatusCode, idempotent: bool) -> bool {
+        match status {
+            StatusCode::TOO_MANY_REQUESTS => true,
+            StatusCode::BAD_GATEWAY
+            | StatusCode::SERVICE_UNAVAILABLE
+            | StatusCode::GATEWAY_TIMEOUT => idempotent,
+            _ => false,
+        }
+    }
+
+    /// The delay before retry number `attempt` (starting at 0).
+    ///
+    /// `Retry-After` and `RateLimit-Reset` headers of gitlab take precedence over the exponential
+    /// backoff, which is randomized between half and the full delay.
+    pub fn delay(&self, attempt: u32, headers: &HeaderMap) -> Duration {
+        if let Some(delay) = requested_delay(headers, Utc::now()) {
+            return delay;
+        }
+
+        let delay = self
+            .base_delay
+            .saturating_mul(2_u64.saturating_pow(attempt))
+            .min(self.max_delay);
+        let jitter = rand::thread_rng().gen_range(0, delay / 2 + 1);
+
+        Duration::from_millis(delay - delay / 2 + jitter)
+    }
+}
+
+/// The delay the server asked for with `Retry-After` (seconds or http date) or `RateLimit-Reset`.
+fn requested_delay(headers: &HeaderMap, now: DateTime<Utc>) -> Option<Duration> {
+    let header = |name| headers.get(name).and_then(|value| value.to_str().ok());
+
+    if let Some(retry_after) = header("retry-after") {
+        if let Ok(seconds) = retry_after.trim().parse::<u64>() {
+            return Some(Duration::from_secs(seconds));
+        }
+        if let Ok(date) = DateTime::parse_from_rfc2822(retry_after.trim()) {
+            return Some(
+                (date.with_timezone(&Utc) - now)
+                    .to_std()
+                    .unwrap_or_default(),
+            );
+        }
+    }
+
+    let reset = header("ratelimit-reset")?.trim().parse::<i64>().ok()?;
+    Some(Duration::from_secs((reset - now.timestamp()).max(0) as u64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use reqwest::header::HeaderValue;
+
+    #[test]
+    fn delay_grows_exponentially_up_to_the_maximum() {
+        let policy = RetryPolicy {
+            max_retries: 10,
+            base_delay: 1000,
+            max_delay: 5000,
+        };
+
+        for (attempt, max) in [(0, 1000), (1, 2000), (2, 4000), (5, 5000)].iter() {
+            let delay = policy.delay(*attempt, &HeaderMap::new()).as_millis() as u64;
+            assert!(
+                delay >= max / 2 && delay <= *max,
+                "{} -> {}",
+                attempt,
+                delay
+            );
+        }
+    }
+
+    #[test]
+    fn only_rate_limits_are_retried_for_requests_that_are_not_idempotent() {
+        assert!(RetryPolicy::is_retryable(StatusCode::BAD_GATEWAY, true));
+        assert!(!RetryPolicy::is_retryable(StatusCode::BAD_GATEWAY, false));
+        assert!(RetryPolicy::is_retryable(
+            StatusCode::TOO_MANY_REQUESTS,
+            false
+        ));
+        assert!(!RetryPolicy::is_retryable(StatusCode::NOT_FOUND, true));
+    }
+
+    #[test]
+    fn requested_delay_honors_the_headers_of_gitlab() {
+        let now = Utc.timestamp(1_600_000_000, 0);
+        let mut headers = HeaderMap::new();
+        headers.insert("ratelimit-reset", HeaderValue::from_static("1600000030"));
+        assert_eq!(
+            requested_delay(&headers, now),
+            Some(Duration::from_secs(30))
+        );
+
+        headers.insert("retry-after", HeaderValue::from_static("7"));
+        assert_eq!(requested_delay(&headers, now), Some(Duration::from_secs(7)));
+
+        assert_eq!(requested_delay(&HeaderMap::new(), now), None);
+    }
+}
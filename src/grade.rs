@@ -0,0 +1,317 @@
+use crate::config::Manifest;
+use crate::files;
+use crate::jobs::Outcome;
+use crate::Error;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const SCORES_DIRECTORY_NAME: &str = "scores";
+
+/// The criteria a feedback is graded by, stored in `<templates>/<name>.rubric.yml`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Rubric {
+    /// Criteria in the order they are listed in feedbacks
+    pub criteria: Vec<Criterion>,
+}
+
+/// Something a project is graded on, e.g. its tests or its documentation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Criterion {
+    /// Key of the criterion in score files
+    pub id: String,
+    /// Title shown in feedbacks
+    pub title: String,
+    /// Points for a perfect solution
+    pub max_points: f64,
+    /// Reusable comments by id, which scores can refer to instead of repeating them
+    #[serde(default)]
+    pub comments: BTreeMap<String, String>,
+}
+
+/// Points and comments of one criterion for one project.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Score {
+    /// `None` until the criterion is graded
+    pub points: Option<f64>,
+    /// Ids of comments of the criterion or free text
+    #[serde(default)]
+    pub comments: Vec<String>,
+}
+
+/// Scores of one project by criterion id, stored in `<feedbacks>/<name>/scores/<key>.yml`.
+pub type Scores = BTreeMap<String, Score>;
+
+/// A validated rubric with the scores of one project, as seen by feedback templates.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Grade {
+    /// Criteria in the order of the rubric
+    pub criteria: Vec<GradedCriterion>,
+    /// Sum of the points of all criteria
+    pub total: f64,
+    /// Sum of the maximum points of all criteria
+    pub max_total: f64,
+}
+
+/// A criterion of the rubric with the points of one project.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct GradedCriterion {
+    /// Key of the criterion in score files
+    pub id: String,
+    /// Title shown in feedbacks
+    pub title: String,
+    /// Points of the project
+    pub points: f64,
+    /// Points for a perfect solution
+    pub max_points: f64,
+    /// Comments with their ids replaced by the text from the rubric
+    pub comments: Vec<String>,
+}
+
+impl Rubric {
+    /// Loads the rubric of the feedback `name`, if it has one.
+    pub fn load(templates_directory: &Path, name: &str) -> anyhow::Result<Option<Self>> {
+        let path = templates_directory.join(format!("{}.rubric.yml", name));
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let rubric: Rubric = serde_yaml::from_str(&fs::read_to_string(&path)?)?;
+
+        let mut ids = BTreeSet::new();
+        for criterion in &rubric.criteria {
+            if !ids.insert(&criterion.id) {
+                return Err(Error::InvalidScore {
+                    criterion: criterion.id.clone(),
+                    message: format!("is listed twice in {}", path.display()),
+                }
+                .into());
+            }
+        }
+
+        Ok(Some(rubric))
+    }
+
+    /// Scores with every criterion left ungraded.
+    pub fn skeleton(&self) -> Scores {
+        self.criteria
+            .iter()
+            .map(|criterion| (criterion.id.clone(), Score::default()))
+            .collect()
+    }
+
+    /// Checks that every criterion is graded within its maximum and computes the total.
+    pub fn grade(&self, scores: &Scores) -> Result<Grade, Error> {
+        let invalid = |criterion: &str, message: String| Error::InvalidScore {
+            criterion: criterion.to_string(),
+            message,
+        };
+
+        if let Some(id) = scores
+            .keys()
+            .find(|id| !self.criteria.iter().any(|criterion| &criterion.id == *id))
+        {
+            return Err(invalid(id, "is not part of the rubric".into()));
+        }
+
+        let mut criteria = Vec::new();
+        for criterion in &self.criteria {
+            let score = scores
+                .get(&criterion.id)
+                .ok_or_else(|| invalid(&criterion.id, "is missing".into()))?;
+            let points = score
+                .points
+                .ok_or_else(|| invalid(&criterion.id, "is not graded yet".into()))?;
+
+            if points < 0.0 || points > criterion.max_points {
+                return Err(invalid(
+                    &criterion.id,
+                    format!(
+                        "has {} points, but only 0 to {} are possible",
+                        points, criterion.max_points
+                    ),
+                ));
+            }
+
+            criteria.push(GradedCriterion {
+                id: criterion.id.clone(),
+                title: criterion.title.clone(),
+                points,
+                max_points: criterion.max_points,
+                comments: score
+                    .comments
+                    .iter()
+                    .map(|comment| criterion.comments.get(comment).unwrap_or(comment).clone())
+                    .collect(),
+            });
+        }
+
+        Ok(Grade {
+            total: criteria.iter().map(|criterion| criterion.points).sum(),
+            max_total: criteria.iter().map(|criterion| criterion.max_points).sum(),
+            criteria,
+        })
+    }
+}
+
+/// Path to the scores of the project `key` for the feedback `name`.
+pub fn scores_path(feedbacks_directory: &Path, name: &str, key: &str) -> PathBuf {
+    feedbacks_directory
+        .join(name)
+        .join(SCORES_DIRECTORY_NAME)
+        .join(format!("{}.yml", key))
+}
+
+/// Loads the scores at `path`, if they exist.
+pub fn load_scores(path: &Path) -> anyhow::Result<Option<Scores>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    Ok(Some(serde_yaml::from_str(&fs::read_to_string(path)?)?))
+}
+
+/// Grades the project `key`, if the feedback `name` has a rubric and the project has scores.
+pub fn grade(config: &Manifest, name: &str, key: &str) -> anyhow::Result<Option<Grade>> {
+    let rubric = match Rubric::load(&config.templates_directory, name)? {
+        Some(rubric) => rubric,
+        None => return Ok(None),
+    };
+
+    match load_scores(&scores_path(&config.feedbacks_directory, name, key))? {
+        Some(scores) => Ok(Some(rubric.grade(&scores)?)),
+        None => Ok(None),
+    }
+}
+
+/// Writes ungraded scores for every project that has no scores yet and returns whether they
+/// were created.
+pub fn create(config: &Manifest, name: &str, dry_run: bool) -> anyhow::Result<Vec<Outcome<bool>>> {
+    let skeleton = serde_yaml::to_string(&required(config, name)?.skeleton())?;
+
+    let directory = config
+        .feedbacks_directory
+        .join(name)
+        .join(SCORES_DIRECTORY_NAME);
+    files::create_dir_all(&directory, dry_run)?;
+
+    Ok(config
+        .projects
+        .keys()
+        .map(|key| {
+            let path = scores_path(&config.feedbacks_directory, name, key);
+            let result = if path.exists() {
+                Ok(false)
+            } else {
+                files::write(&path, &skeleton, dry_run).map(|_| true)
+            };
+
+            Outcome {
+                key: key.clone(),
+                result,
+            }
+        })
+        .collect())
+}
+
+/// Validates the scores of every project against the rubric.
+pub fn check(config: &Manifest, name: &str) -> anyhow::Result<Vec<Outcome<Grade>>> {
+    let rubric = required(config, name)?;
+
+    Ok(config
+        .projects
+        .keys()
+        .map(|key| {
+            let path = scores_path(&config.feedbacks_directory, name, key);
+            let result = match load_scores(&path) {
+                Ok(Some(scores)) => rubric.grade(&scores).map_err(anyhow::Error::from),
+                Ok(None) => Err(anyhow::anyhow!("{} is missing", path.display())),
+                Err(e) => Err(e),
+            };
+
+            Outcome {
+                key: key.clone(),
+                result,
+            }
+        })
+        .collect())
+}
+
+fn required(config: &Manifest, name: &str) -> anyhow::Result<Rubric> {
+    Rubric::load(&config.templates_directory, name)?
+        .ok_or_else(|| anyhow::anyhow!("the feedback {} has no `{}.rubric.yml`", name, name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rubric() -> Rubric {
+        serde_yaml::from_str(
+            "Criteria:
+  - Id: correctness
+    Title: Correctness
+    MaxPoints: 10
+    Comments:
+      edge: Some edge cases fail.
+  - Id: style
+    Title: Style
+    MaxPoints: 2.5
+",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn grade_sums_points_and_expands_comments() {
+        let scores: Scores = serde_yaml::from_str(
+            "correctness:
+  Points: 8
+  Comments: [edge, Well structured.]
+style:
+  Points: 2.5
+",
+        )
+        .unwrap();
+
+        let grade = rubric().grade(&scores).unwrap();
+
+        assert_eq!(grade.total, 10.5);
+        assert_eq!(grade.max_total, 12.5);
+        assert_eq!(
+            grade.criteria[0].comments,
+            vec!["Some edge cases fail.", "Well structured."]
+        );
+    }
+
+    #[test]
+    fn grade_rejects_ungraded_unknown_and_too_many_points() {
+        let rubric = rubric();
+        let error = |scores: &str| {
+            rubric
+                .grade(&serde_yaml::from_str(scores).unwrap())
+                .unwrap_err()
+                .to_string()
+        };
+
+        assert_eq!(
+            error(&serde_yaml::to_string(&rubric.skeleton()).unwrap()),
+            "`correctness` is not graded yet"
+        );
+        assert_eq!(
+            error("correctness: {Points: 11}\nstyle: {Points: 1}"),
+            "`correctness` has 11 points, but only 0 to 10 are possible"
+        );
+        assert_eq!(
+            error("correctness: {Points: 1}\nstyle: {Points: 1}\nbonus: {Points: 1}"),
+            "`bonus` is not part of the rubric"
+        );
+        assert_eq!(error("correctness: {Points: 1}"), "`style` is missing");
+    }
+}
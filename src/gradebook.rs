@@ -0,0 +1,233 @@
+use crate::config::Manifest;
+use crate::csv;
+use crate::grade::{self, Rubric};
+use crate::Error;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+
+/// Points of every student on every graded feedback.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Gradebook {
+    /// Names of all feedbacks with a rubric, in alphabetical order
+    pub sheets: Vec<String>,
+    /// All members of all projects, by username
+    pub students: Vec<Student>,
+    /// Projects whose scores are incomplete, invalid or malformed and were left out
+    #[serde(skip)]
+    pub warnings: Vec<String>,
+}
+
+/// A row of the gradebook.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Student {
+    /// Username on gitlab
+    pub username: String,
+    /// Full name on gitlab
+    pub name: String,
+    /// Keys of all projects the student is a member of
+    pub projects: Vec<String>,
+    /// Total points by feedback name, `None` if the project wasn't graded yet
+    pub points: BTreeMap<String, Option<f64>>,
+    /// Sum of all points
+    pub total: f64,
+}
+
+impl Gradebook {
+    /// Collects the totals of all score files in `feedbacks_directory` and assigns them to every
+    /// member of the project.
+    pub fn new(config: &Manifest) -> anyhow::Result<Self> {
+        let mut sheets = Vec::new();
+        if config.feedbacks_directory.exists() {
+            for entry in fs::read_dir(&config.feedbacks_directory)? {
+                let entry = entry?;
+                let name = entry.file_name().to_string_lossy().into_owned();
+                if entry.file_type()?.is_dir()
+                    && Rubric::load(&config.templates_directory, &name)?.is_some()
+                {
+                    sheets.push(name);
+                }
+            }
+        }
+        sheets.sort();
+
+        let mut students: BTreeMap<String, Student> = BTreeMap::new();
+        let mut warnings = Vec::new();
+        for (key, project) in &config.projects {
+            let mut points = BTreeMap::new();
+            for name in &sheets {
+                let total = match grade::grade(config, name, key) {
+                    Ok(grade) => grade.map(|grade| grade.total),
+                    // e.g. a project whose grading has just started, or a typo in its scores
+                    Err(e) if e.is::<Error>() || e.is::<serde_yaml::Error>() => {
+                        warnings.push(format!("{} of {}: {}", name, key, e));
+                        None
+                    }
+                    Err(e) => return Err(anyhow::anyhow!("{} of {}: {}", name, key, e)),
+                };
+                points.insert(name.clone(), total);
+            }
+
+            for member in &project.members {
+                let student = students
+                    .entry(member.username.clone())
+                    .or_insert_with(|| Student {
+                        username: member.username.clone(),
+                        name: member.name.clone(),
+                        projects: Vec::new(),
+                        points: sheets.iter().map(|name| (name.clone(), None)).collect(),
+                        total: 0.0,
+                    });
+                student.projects.push(key.clone());
+
+                // a student who switched teams keeps the points of the first graded project
+                for (name, points) in &points {
+                    let entry = student.points.entry(name.clone()).or_default();
+                    if entry.is_none() {
+                        *entry = *points;
+                    }
+                }
+            }
+        }
+
+        let students = students
+            .into_values()
+            .map(|mut student| {
+                // `sum` of no points is -0
+                student.total = student.points.values().flatten().fold(0.0, |a, b| a + b);
+                student
+            })
+            .collect();
+
+        Ok(Gradebook {
+            sheets,
+            students,
+            warnings,
+        })
+    }
+
+    /// One row per student with the columns `Username`, `Name`, `Projects`, one column per sheet
+    /// and `Total`.
+    pub fn to_csv(&self) -> String {
+        let mut header = vec!["Username".to_string(), "Name".into(), "Projects".into()];
+        header.extend(self.sheets.iter().cloned());
+        header.push("Total".into());
+
+        let mut csv = csv::row(&header);
+        for student in &self.students {
+            let mut cells = vec![
+                student.username.clone(),
+                student.name.clone(),
+                student.projects.join(" "),
+            ];
+            cells.extend(self.sheets.iter().map(|name| {
+                student
+                    .points
+                    .get(name)
+                    .copied()
+                    .flatten()
+                    .map(|points| points.to_string())
+                    .unwrap_or_default()
+            }));
+            cells.push(student.total.to_string());
+            csv.push_str(&csv::row(&cells));
+        }
+
+        csv
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Member, Project};
+
+    fn project(id: u32, members: &[(&str, &str)]) -> Project {
+        Project {
+            id,
+            members: members
+                .iter()
+                .map(|(username, name)| Member {
+                    username: username.to_string(),
+                    name: name.to_string(),
+                })
+                .collect(),
+            repository: format!("git@example.com:{}/exercise.git", id),
+            web_url: None,
+            extra: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn gradebook_has_one_row_per_student() {
+        let directory = tempfile::tempdir().unwrap();
+        let mut config = Manifest::new(Some(1));
+        config.templates_directory = directory.path().join("templates");
+        config.feedbacks_directory = directory.path().join("feedbacks");
+        config.projects.insert(
+            "team-a".into(),
+            project(10, &[("alice", "Alice"), ("bob", "Smith, Bob")]),
+        );
+        config
+            .projects
+            .insert("team-b".into(), project(11, &[("carol", "Carol")]));
+        config
+            .projects
+            .insert("team-c".into(), project(12, &[("dave", "Dave")]));
+
+        fs::create_dir_all(&config.templates_directory).unwrap();
+        for sheet in &["sheet-1", "sheet-2"] {
+            fs::write(
+                config
+                    .templates_directory
+                    .join(format!("{}.rubric.yml", sheet)),
+                "Criteria:\n  - {Id: tests, Title: Tests, MaxPoints: 10}\n",
+            )
+            .unwrap();
+            fs::create_dir_all(config.feedbacks_directory.join(sheet).join("scores")).unwrap();
+        }
+        fs::create_dir_all(config.feedbacks_directory.join("announcement")).unwrap();
+        let scores = |sheet: &str, key: &str, points: &str| {
+            fs::write(
+                grade::scores_path(&config.feedbacks_directory, sheet, key),
+                format!("tests: {{Points: {}}}", points),
+            )
+            .unwrap();
+        };
+        scores("sheet-1", "team-a", "7.5");
+        scores("sheet-2", "team-a", "10");
+        scores("sheet-1", "team-b", "4");
+        scores("sheet-1", "team-c", "[7");
+        // written by `grade create`, but not graded yet
+        let skeleton = Rubric::load(&config.templates_directory, "sheet-2")
+            .unwrap()
+            .unwrap()
+            .skeleton();
+        fs::write(
+            grade::scores_path(&config.feedbacks_directory, "sheet-2", "team-b"),
+            serde_yaml::to_string(&skeleton).unwrap(),
+        )
+        .unwrap();
+
+        let gradebook = Gradebook::new(&config).unwrap();
+
+        assert_eq!(gradebook.sheets, vec!["sheet-1", "sheet-2"]);
+        assert_eq!(gradebook.warnings.len(), 2);
+        assert_eq!(
+            gradebook.warnings[0],
+            "sheet-2 of team-b: `tests` is not graded yet"
+        );
+        assert!(gradebook.warnings[1].starts_with("sheet-1 of team-c: "));
+        assert_eq!(
+            gradebook.to_csv(),
+            "Username,Name,Projects,sheet-1,sheet-2,Total
+alice,Alice,team-a,7.5,10,17.5
+bob,\"Smith, Bob\",team-a,7.5,10,17.5
+carol,Carol,team-b,4,,4
+dave,Dave,team-c,,,0
+"
+        );
+    }
+}
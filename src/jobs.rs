@@ -0,0 +1,200 @@
+use futures::stream::{self, StreamExt};
+use std::future::Future;
+use tokio::sync::Mutex;
+use tokio::time::{self, Duration, Instant};
+
+/// Spaces out the start of jobs without blocking the runtime.
+pub struct RateLimiter {
+    interval: Duration,
+    next: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    /// A limiter that lets the first job start right away.
+    pub fn new(interval: Duration) -> Self {
+        RateLimiter {
+            interval,
+            next: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Waits until the next slot is free and reserves it.
+    pub async fn wait(&self) {
+        let mut next = self.next.lock().await;
+        if *next > Instant::now() {
+            time::delay_until(*next).await;
+        }
+        *next = Instant::now() + self.interval;
+    }
+}
+
+/// Result of a single job, identified by the key of its project.
+pub struct Outcome<R> {
+    /// Key of the project
+    pub key: String,
+    /// What the job returned
+    pub result: anyhow::Result<R>,
+}
+
+/// Executes one job per project with at most `jobs` of them running at once.
+pub struct Runner {
+    jobs: usize,
+    limiter: RateLimiter,
+}
+
+impl Runner {
+    /// Runs at most `jobs` jobs at once (at least one) and starts them at least `delay` apart.
+    pub fn new(jobs: usize, delay: Duration) -> Self {
+        Runner {
+            jobs: jobs.max(1),
+            limiter: RateLimiter::new(delay),
+        }
+    }
+
+    /// Runs `job` for every `(key, item)` pair and returns the outcomes sorted by key.
+    pub async fn run<I, T, R, F, Fut>(&self, items: I, job: F) -> Vec<Outcome<R>>
+    where
+        I: IntoIterator<Item = (String, T)>,
+        F: Fn(String, T) -> Fut,
+        Fut: Future<Output = anyhow::Result<R>>,
+    {
+        let job = &job;
+        let mut outcomes: Vec<Outcome<R>> = stream::iter(items)
+            .map(|(key, item)| async move {
+                self.limiter.wait().await;
+                log::info!("starting job for {}", key);
+                let result = job(key.clone(), item).await;
+                Outcome { key, result }
+            })
+            .buffer_unordered(self.jobs)
+            .collect()
+            .await;
+
+        outcomes.sort_by(|a, b| a.key.cmp(&b.key));
+        outcomes
+    }
+}
+
+/// Number of projects whose job failed.
+pub fn failed<R>(outcomes: &[Outcome<R>]) -> usize {
+    outcomes
+        .iter()
+        .filter(|outcome| outcome.result.is_err())
+        .count()
+}
+
+/// Prints one line per project with its status and either `describe(result)` or the error, and
+/// returns the number of failed projects.
+pub fn print_table<R, D>(outcomes: &[Outcome<R>], describe: D) -> usize
+where
+    D: Fn(&R) -> String,
+{
+    let width = outcomes
+        .iter()
+        .map(|outcome| outcome.key.len())
+        .max()
+        .unwrap_or(0)
+        .max("project".len());
+
+    println!("{:width$}  status", "project", width = width);
+
+    for outcome in outcomes {
+        let (status, message) = match &outcome.result {
+            Ok(value) => ("\u{2713}", describe(value)),
+            Err(e) => {
+                log::debug!("{}: {:?}", outcome.key, e);
+                (
+                    "\u{2715}",
+                    e.to_string()
+                        .split_whitespace()
+                        .collect::<Vec<_>>()
+                        .join(" "),
+                )
+            }
+        };
+        let line = format!(
+            "{:width$}  {}  {}",
+            outcome.key,
+            status,
+            message,
+            width = width
+        );
+        println!("{}", line.trim_end());
+    }
+
+    let failed = failed(outcomes);
+    println!("{} succeeded, {} failed", outcomes.len() - failed, failed);
+    failed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+
+    fn items(count: usize) -> Vec<(String, usize)> {
+        (0..count).map(|i| (format!("team-{}", i), i)).collect()
+    }
+
+    #[tokio::test]
+    async fn runner_runs_at_most_jobs_at_once() {
+        time::pause();
+        let runner = Runner::new(2, Duration::from_millis(0));
+        let running = AtomicUsize::new(0);
+        let most = AtomicUsize::new(0);
+
+        let outcomes = runner
+            .run(items(6), |_, i| {
+                let (running, most) = (&running, &most);
+                async move {
+                    let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                    most.fetch_max(now, Ordering::SeqCst);
+                    time::delay_for(Duration::from_millis(10)).await;
+                    running.fetch_sub(1, Ordering::SeqCst);
+                    Ok(i)
+                }
+            })
+            .await;
+
+        assert_eq!(most.load(Ordering::SeqCst), 2);
+        assert_eq!(
+            outcomes
+                .iter()
+                .map(|outcome| *outcome.result.as_ref().unwrap())
+                .collect::<Vec<_>>(),
+            vec![0, 1, 2, 3, 4, 5]
+        );
+    }
+
+    #[tokio::test]
+    async fn rate_limiter_spaces_out_the_start_of_jobs() {
+        time::pause();
+        let start = Instant::now();
+        let runner = Runner::new(4, Duration::from_millis(100));
+        let starts = Mutex::new(Vec::new());
+
+        runner
+            .run(items(4), |_, _| {
+                let starts = &starts;
+                async move {
+                    starts.lock().unwrap().push(Instant::now() - start);
+                    Ok(())
+                }
+            })
+            .await;
+
+        let mut starts = starts.into_inner().unwrap();
+        starts.sort();
+        assert_eq!(starts[0], Duration::from_millis(0));
+        // the timer wheel rounds up to the next millisecond
+        for pair in starts.windows(2) {
+            let gap = pair[1] - pair[0];
+            assert!(
+                gap >= Duration::from_millis(100) && gap < Duration::from_millis(102),
+                "{:?}",
+                starts
+            );
+        }
+    }
+}
@@ -0,0 +1,54 @@
+use chrono::{DateTime, FixedOffset};
+use serde::{Deserialize, Serialize};
+
+/// ``` json
+/// {
+///   "id": 4,
+///   "project_id": 1,
+///   "action_name": "pushed to",
+///   "target_id": null,
+///   "target_type": null,
+///   "author_id": 1,
+///   "author": {
+///     "name": "Dmitriy Zaporozhets",
+///     "username": "root",
+///     "id": 1,
+///     "state": "active",
+///     "avatar_url": "http://localhost:3000/uploads/user/avatar/1/fox_avatar.png",
+///     "web_url": "http://localhost:3000/root"
+///   },
+///   "author_username": "root",
+///   "created_at": "2017-02-09T10:43:19.667Z",
+///   "push_data": {
+///     "commit_count": 1,
+///     "action": "pushed",
+///     "ref_type": "branch",
+///     "commit_from": "50d4420237a9de7be1304607147aec22e4a14af7",
+///     "commit_to": "c5feabde2d8cd023215af4d2ceeb7a64839fc428",
+///     "ref": "master",
+///     "commit_title": "Add simple search to projects in public area"
+///   }
+/// }
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Event {
+    /// What happened, e.g. `pushed to`
+    pub action_name: Option<String>,
+    /// Username of the user who caused the event
+    pub author_username: Option<String>,
+    /// When the event happened
+    pub created_at: DateTime<FixedOffset>,
+    /// Details of a push, `None` for other events
+    pub push_data: Option<PushData>,
+}
+
+/// What a push event changed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PushData {
+    /// Number of pushed commits
+    #[serde(default)]
+    pub commit_count: usize,
+    /// The pushed branch or tag
+    #[serde(rename = "ref")]
+    pub ref_name: Option<String>,
+}
@@ -1,6 +1,4 @@
-use reqwest::Client;
 use serde::{Deserialize, Serialize};
-use std::fmt::Display;
 
 /// ``` json
 /// {
@@ -66,119 +64,82 @@ use std::fmt::Display;
 ///   }
 /// }
 /// ```
-#[derive(Debug, Serialize, Deserialize)]
+/// Only `id`, `namespace` and `ssh_url_to_repo` are required, so that forks without a readme,
+/// shared forks and other versions of gitlab can be deserialized as well.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Fork {
+    /// Id of the project
     pub id: u32,
-    pub description: Option<String>,
-    pub default_branch: String,
-    pub visibility: String,
-    pub ssh_url_to_repo: String,
-    pub http_url_to_repo: String,
-    pub web_url: String,
-    pub readme_url: String,
-    pub tag_list: Vec<String>,
-    pub name: String,
-    pub name_with_namespace: String,
-    pub path: String,
-    pub path_with_namespace: String,
-    pub issues_enabled: bool,
-    pub open_issues_count: usize,
-    pub merge_requests_enabled: bool,
-    pub jobs_enabled: bool,
-    pub wiki_enabled: bool,
-    pub snippets_enabled: bool,
-    pub can_create_merge_request_in: bool,
-    pub resolve_outdated_diff_discussions: bool,
-    pub container_registry_enabled: bool,
-    pub created_at: String,
-    pub last_activity_at: String,
-    pub creator_id: usize,
+    /// The user or group the project belongs to
     pub namespace: Namespace,
-    pub import_status: String,
-    pub archived: bool,
-    pub avatar_url: Option<String>,
-    pub shared_runners_enabled: bool,
-    pub forks_count: usize,
-    pub star_count: usize,
-    pub public_jobs: bool,
-    pub shared_with_groups: Vec<String>,
-    pub only_allow_merge_if_pipeline_succeeds: bool,
-    pub allow_merge_on_skipped_pipeline: Option<bool>,
-    pub only_allow_merge_if_all_discussions_are_resolved: bool,
-    pub remove_source_branch_after_merge: bool,
-    pub request_access_enabled: bool,
-    pub merge_method: String,
-    pub autoclose_referenced_issues: bool,
-    pub suggestion_commit_message: Option<String>,
-    pub _links: Links,
+    /// Url to clone the project with ssh
+    pub ssh_url_to_repo: String,
+    /// Url to clone the project with https
+    pub http_url_to_repo: Option<String>,
+    /// Url of the project page
+    pub web_url: Option<String>,
+    /// Default branch, `None` for an empty repository
+    pub default_branch: Option<String>,
+    /// Display name of the project
+    pub name: Option<String>,
+    /// Last segment of the url of the project
+    pub path: Option<String>,
+    /// Full path of the project, e.g. `course/ws20/team-a`
+    pub path_with_namespace: Option<String>,
+    /// Description of the project
+    pub description: Option<String>,
+    /// Creation time in ISO 8601
+    pub created_at: Option<String>,
+    /// Time of the last activity in ISO 8601
+    pub last_activity_at: Option<String>,
+    /// Whether the project is archived
+    pub archived: Option<bool>,
+    /// Api urls of related resources
+    pub _links: Option<Links>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Api urls of the resources of a project.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Links {
+    /// The project itself
     #[serde(rename = "self")]
-    _self: String,
-    issues: String,
-    merge_requests: String,
-    repo_branches: String,
-    labels: String,
-    events: String,
-    members: String,
+    pub _self: Option<String>,
+    /// Issues of the project
+    pub issues: Option<String>,
+    /// Merge requests of the project
+    pub merge_requests: Option<String>,
+    /// Branches of the repository
+    pub repo_branches: Option<String>,
+    /// Labels of the project
+    pub labels: Option<String>,
+    /// Events of the project
+    pub events: Option<String>,
+    /// Members of the project
+    pub members: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// The user or group a project belongs to.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Namespace {
+    /// Id of the namespace
     pub id: u32,
-    pub name: String,
+    /// Last segment of the path, e.g. `team-a`
     pub path: String,
-    pub kind: String,
-    pub full_path: String,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct Forks(Vec<Fork>);
-
-impl IntoIterator for Forks {
-    type Item = Fork;
-    type IntoIter = std::vec::IntoIter<Self::Item>;
-
-    fn into_iter(self) -> Self::IntoIter {
-        self.0.into_iter()
-    }
+    /// Display name of the namespace
+    pub name: Option<String>,
+    /// `user` or `group`
+    pub kind: Option<String>,
+    /// Full path, e.g. `course/team-a`
+    pub full_path: Option<String>,
 }
 
-impl Forks {
-    pub async fn get<G: AsRef<str> + Display>(
-        client: &Client,
-        gitlab_api_url: G,
-        project_id: u32,
-    ) -> anyhow::Result<Forks> {
-        let mut list = Vec::new();
-
-        for page_id in 1..10 as usize {
-            let res = client
-                .get(&format!(
-                    "{}/v4/projects/{}/forks",
-                    &gitlab_api_url, &project_id
-                ))
-                .query(&[
-                    ("order_by", "created_at"),
-                    ("per_page", "100"),
-                    ("sort", "asc"),
-                    ("page", page_id.to_string().as_str()),
-                ])
-                .send()
-                .await?;
-
-            let data = res.text().await?.replace("\n", " ");
-            let current: Vec<Fork> = serde_json::from_str(&data)?;
-
-            if current.is_empty() {
-                break;
-            }
-
-            list.extend(current);
-        }
-
-        Ok(Forks(list))
-    }
+/// Body of `POST /projects/:id/fork`.
+#[derive(Debug, Clone, Serialize)]
+pub struct NewForkRequest {
+    /// Full path of the namespace the fork is created in
+    pub namespace_path: String,
+    /// Path of the fork inside the namespace
+    pub path: String,
+    /// Display name of the fork
+    pub name: String,
 }
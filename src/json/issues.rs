@@ -1,11 +1,13 @@
-use reqwest::Client;
 use serde::{Deserialize, Serialize, Serializer};
-use std::fmt::Display;
 
-#[derive(Debug, Serialize)]
+/// Body of `POST /projects/:id/issues`.
+#[derive(Debug, Clone, Serialize)]
 pub struct NewIssueRequest {
+    /// Title of the issue
     pub title: String,
+    /// Markdown body of the issue
     pub description: String,
+    /// Labels, sent as a comma separated list
     #[serde(serialize_with = "labels_serialize")]
     pub labels: Vec<String>,
 }
@@ -17,26 +19,7 @@ where
     s.serialize_str(&labels.join(","))
 }
 
-impl NewIssueRequest {
-    pub async fn post<G: AsRef<str> + Display>(
-        self,
-        client: &Client,
-        gitlab_api_url: G,
-        project_id: u32,
-    ) -> anyhow::Result<NewIssueResponse> {
-        Ok(client
-            .post(&format!(
-                "{}/projects/{}/issues",
-                gitlab_api_url, project_id
-            ))
-            .json(&self)
-            .send()
-            .await?
-            .json()
-            .await?)
-    }
-}
-
+/// ``` json
 /// {
 ///   "project_id" : 4,
 ///   "id" : 84,
@@ -46,13 +29,46 @@ impl NewIssueRequest {
 ///   "state" : "opened"
 ///   ...
 /// }
+/// ```
 #[derive(Debug, Deserialize)]
 pub struct NewIssueResponse {
-    state: String,
+    /// Id of the issue within its project
+    pub iid: u32,
+    /// `opened` or `closed`
+    pub state: String,
 }
 
 impl NewIssueResponse {
+    /// Whether the issue is open.
     pub fn is_opened(&self) -> bool {
         self.state == "opened"
     }
 }
+
+/// Body of `PUT /projects/:id/issues/:issue_iid`.
+#[derive(Debug, Serialize)]
+pub struct EditIssueRequest {
+    /// New title of the issue
+    pub title: String,
+    /// New markdown body of the issue
+    pub description: String,
+}
+
+/// ``` json
+/// {
+///   "project_id" : 4,
+///   "id" : 84,
+///   "iid" : 14,
+///   "title" : "Issues with auth",
+///   "description" : "Lorem ipsum dolor sit amet",
+///   "state" : "opened"
+///   ...
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Issue {
+    /// Title of the issue
+    pub title: String,
+    /// Markdown body of the issue, `None` if it is empty
+    pub description: Option<String>,
+}
@@ -1,6 +1,7 @@
-use reqwest::Client;
-use serde::{Deserialize, Serialize};
-use std::fmt::Display;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
 
 /// ``` json
 /// [
@@ -33,26 +34,137 @@ use std::fmt::Display;
 ///   }
 /// ]
 /// ```
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Member {
+    /// Id of the user
+    #[serde(default)]
+    pub id: u32,
+    /// Username of the user
     pub username: String,
+    /// Full name of the user
+    #[serde(default)]
     pub name: String,
+    /// `None` for access levels this version does not know yet, so they don't fail the whole
+    /// member list.
+    #[serde(default, deserialize_with = "known_access_level")]
+    pub access_level: Option<AccessLevel>,
 }
 
-impl Member {
-    pub async fn get<G: AsRef<str> + Display>(
-        client: &Client,
-        gitlab_api_url: G,
-        project_id: u32,
-    ) -> anyhow::Result<Vec<Member>> {
-        let res = client
-            .get(&format!(
-                "{}/v4/projects/{}/members",
-                gitlab_api_url, project_id
-            ))
-            .send()
-            .await?;
-
-        Ok(res.json().await?)
+fn known_access_level<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<AccessLevel>, D::Error> {
+    let value = Option::<u32>::deserialize(deserializer)?;
+    Ok(value.and_then(|value| AccessLevel::try_from(value).ok()))
+}
+
+/// Body of `POST /projects/:id/members`.
+#[derive(Debug, Clone, Serialize)]
+pub struct NewMemberRequest {
+    /// Id of the user to add
+    pub user_id: u32,
+    /// Access level of the new member
+    pub access_level: AccessLevel,
+}
+
+/// Body of `PUT /projects/:id/members/:user_id`.
+#[derive(Debug, Clone, Serialize)]
+pub struct EditMemberRequest {
+    /// New access level of the member
+    pub access_level: AccessLevel,
+}
+
+/// Permissions of a member, serialized as the number gitlab uses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(into = "u32", try_from = "u32")]
+pub enum AccessLevel {
+    /// No access to the project
+    NoAccess = 0,
+    /// Only visible as a member
+    MinimalAccess = 5,
+    /// Read issues and comment
+    Guest = 10,
+    /// Manage issues and plans
+    Planner = 15,
+    /// Read the code
+    Reporter = 20,
+    /// Push to unprotected branches
+    Developer = 30,
+    /// Manage the project and push to protected branches
+    Maintainer = 40,
+    /// Owns the project or its group
+    Owner = 50,
+}
+
+const ACCESS_LEVELS: [(AccessLevel, &str); 8] = [
+    (AccessLevel::NoAccess, "no-access"),
+    (AccessLevel::MinimalAccess, "minimal-access"),
+    (AccessLevel::Guest, "guest"),
+    (AccessLevel::Planner, "planner"),
+    (AccessLevel::Reporter, "reporter"),
+    (AccessLevel::Developer, "developer"),
+    (AccessLevel::Maintainer, "maintainer"),
+    (AccessLevel::Owner, "owner"),
+];
+
+impl From<AccessLevel> for u32 {
+    fn from(level: AccessLevel) -> u32 {
+        level as u32
+    }
+}
+
+impl TryFrom<u32> for AccessLevel {
+    type Error = String;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        ACCESS_LEVELS
+            .iter()
+            .map(|(level, _)| *level)
+            .find(|level| *level as u32 == value)
+            .ok_or_else(|| format!("unknown access level {}", value))
+    }
+}
+
+impl FromStr for AccessLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ACCESS_LEVELS
+            .iter()
+            .find(|(_, name)| *name == s)
+            .map(|(level, _)| *level)
+            .ok_or_else(|| {
+                let names: Vec<_> = ACCESS_LEVELS.iter().map(|(_, name)| *name).collect();
+                format!("`{}` is not one of {}", s, names.join(", "))
+            })
+    }
+}
+
+impl fmt::Display for AccessLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (_, name) = ACCESS_LEVELS
+            .iter()
+            .find(|(level, _)| level == self)
+            .expect("every access level has a name");
+        write!(f, "{}", name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unknown_access_levels_are_none() {
+        let members: Vec<Member> = serde_json::from_str(
+            r#"[
+                {"id": 1, "username": "alice", "access_level": 15},
+                {"id": 2, "username": "bob", "access_level": 35},
+                {"id": 3, "username": "carol"}
+            ]"#,
+        )
+        .unwrap();
+
+        let levels: Vec<_> = members.iter().map(|member| member.access_level).collect();
+        assert_eq!(levels, vec![Some(AccessLevel::Planner), None, None]);
     }
 }
@@ -0,0 +1,35 @@
+use serde::{Deserialize, Serialize};
+
+/// Body of `POST /projects/:id/merge_requests`.
+#[derive(Debug, Clone, Serialize)]
+pub struct NewMergeRequest {
+    /// Branch with the changes
+    pub source_branch: String,
+    /// Branch the changes are merged into
+    pub target_branch: String,
+    /// Title of the merge request
+    pub title: String,
+    /// Markdown body of the merge request
+    pub description: String,
+    /// Delete the source branch after merging
+    pub remove_source_branch: bool,
+}
+
+/// ``` json
+/// {
+///   "id": 1,
+///   "iid": 1,
+///   "project_id": 3,
+///   "title": "test1",
+///   "state": "opened",
+///   "web_url": "http://gitlab.example.com/my-group/my-project/merge_requests/1",
+///   ...
+/// }
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+pub struct MergeRequest {
+    /// Id of the merge request within its project
+    pub iid: u32,
+    /// Url of the merge request page
+    pub web_url: Option<String>,
+}
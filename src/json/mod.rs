@@ -1,8 +1,17 @@
+mod events;
+pub use events::{Event, PushData};
+
 mod forks;
-pub use forks::{Fork, Forks};
+pub use forks::{Fork, Links, Namespace, NewForkRequest};
 
 mod members;
-pub use members::Member;
+pub use members::{AccessLevel, EditMemberRequest, Member, NewMemberRequest};
+
+mod merge_requests;
+pub use merge_requests::{MergeRequest, NewMergeRequest};
 
 mod issues;
-pub use issues::{NewIssueRequest, NewIssueResponse};
+pub use issues::{EditIssueRequest, Issue, NewIssueRequest, NewIssueResponse};
+
+mod version;
+pub use version::Version;
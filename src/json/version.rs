@@ -0,0 +1,15 @@
+use serde::{Deserialize, Serialize};
+
+/// ``` json
+/// {
+///   "version": "8.13.0-pre",
+///   "revision": "4e963fe"
+/// }
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Version {
+    /// Version of gitlab, e.g. `13.6.1`
+    pub version: String,
+    /// Git revision of the release
+    pub revision: String,
+}
@@ -0,0 +1,235 @@
+use crate::config::{Member, Project};
+use crate::git::{self, Commit};
+use crate::gitlab::GitlabApi;
+use crate::json::Event;
+use chrono::{DateTime, FixedOffset};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::path::Path;
+
+/// Activity of a single project after the deadline.
+#[derive(Debug, Default)]
+pub struct Late {
+    /// Commits after the deadline
+    pub commits: usize,
+    /// Usernames (or names) of their authors
+    pub authors: BTreeSet<String>,
+    /// Committer date of the latest of them
+    pub latest_commit: Option<DateTime<FixedOffset>>,
+    /// Push events reported by gitlab, `None` if they were not queried
+    pub pushes: Option<Pushes>,
+}
+
+/// Pushes to a project after the deadline, as reported by gitlab.
+#[derive(Debug, Default)]
+pub struct Pushes {
+    /// Number of pushes
+    pub count: usize,
+    /// Usernames of the users who pushed
+    pub pushers: BTreeSet<String>,
+    /// Time of the latest push
+    pub latest: Option<DateTime<FixedOffset>>,
+}
+
+impl Late {
+    /// Summarizes the `commits` and push `events` of a project after `deadline`.
+    pub fn new(
+        members: &[Member],
+        aliases: &BTreeMap<String, String>,
+        commits: &[Commit],
+        events: Option<&[Event]>,
+        deadline: &DateTime<FixedOffset>,
+    ) -> Self {
+        let commits: Vec<&Commit> = commits
+            .iter()
+            .filter(|commit| commit.date > *deadline)
+            .collect();
+
+        let pushes = events.map(|events| {
+            let events: Vec<&Event> = events
+                .iter()
+                .filter(|event| event.push_data.is_some() && event.created_at > *deadline)
+                .collect();
+
+            Pushes {
+                count: events.len(),
+                pushers: events
+                    .iter()
+                    .filter_map(|event| event.author_username.clone())
+                    .collect(),
+                latest: events.iter().map(|event| event.created_at).max(),
+            }
+        });
+
+        Late {
+            commits: commits.len(),
+            authors: commits
+                .iter()
+                .map(|commit| member_of(members, aliases, commit))
+                .collect(),
+            latest_commit: commits.iter().map(|commit| commit.date).max(),
+            pushes,
+        }
+    }
+
+    /// Whether anything was committed or pushed after the deadline.
+    pub fn is_late(&self) -> bool {
+        self.commits > 0 || self.pushes.as_ref().is_some_and(|p| p.count > 0)
+    }
+}
+
+/// Inspects the clone in `dir` and, if `api` is given, the push events of the project.
+pub async fn check(
+    api: Option<&dyn GitlabApi>,
+    dir: &Path,
+    project: &Project,
+    aliases: &BTreeMap<String, String>,
+    deadline: &DateTime<FixedOffset>,
+) -> anyhow::Result<Late> {
+    git::git(dir, &["fetch", "origin"]).await?;
+    let commits = git::commits_since(dir, deadline).await?;
+
+    let events = match api {
+        Some(api) => {
+            let after = deadline.date().naive_local().pred();
+            Some(api.push_events(project.id, after).await?)
+        }
+        None => None,
+    };
+
+    Ok(Late::new(
+        &project.members,
+        aliases,
+        &commits,
+        events.as_deref(),
+        deadline,
+    ))
+}
+
+/// Maps the author of a commit to the username of a member, falling back to the author's name.
+pub fn member_of(
+    members: &[Member],
+    aliases: &BTreeMap<String, String>,
+    commit: &Commit,
+) -> String {
+    username_of(members, aliases, commit).unwrap_or_else(|| commit.author_name.clone())
+}
+
+/// Maps the author of a commit to a username, either by `aliases` (author names or emails to
+/// usernames) or by the name of a member or the local part of the email.
+pub fn username_of(
+    members: &[Member],
+    aliases: &BTreeMap<String, String>,
+    commit: &Commit,
+) -> Option<String> {
+    if let Some(username) = aliases
+        .get(&commit.author_email)
+        .or_else(|| aliases.get(&commit.author_name))
+    {
+        return Some(username.clone());
+    }
+
+    let local_part = commit.author_email.split('@').next().unwrap_or_default();
+    members
+        .iter()
+        .find(|member| member.name == commit.author_name || member.username == local_part)
+        .map(|member| member.username.clone())
+}
+
+fn join(names: &BTreeSet<String>) -> String {
+    names.iter().cloned().collect::<Vec<_>>().join(", ")
+}
+
+impl fmt::Display for Late {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.is_late() {
+            return write!(f, "on time");
+        }
+
+        let mut parts = Vec::new();
+        if let Some(latest) = self.latest_commit {
+            parts.push(format!(
+                "{} commit(s) by {} (latest {})",
+                self.commits,
+                join(&self.authors),
+                latest.to_rfc3339()
+            ));
+        }
+        if let Some(Pushes {
+            count,
+            pushers,
+            latest: Some(latest),
+        }) = &self.pushes
+        {
+            parts.push(format!(
+                "{} push(es) by {} (latest {})",
+                count,
+                join(pushers),
+                latest.to_rfc3339()
+            ));
+        }
+        write!(f, "late: {}", parts.join("; "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gitlab::MockGitlab;
+    use crate::json::PushData;
+    use chrono::NaiveDate;
+
+    fn time(s: &str) -> DateTime<FixedOffset> {
+        DateTime::parse_from_rfc3339(s).unwrap()
+    }
+
+    #[tokio::test]
+    async fn late_counts_commits_and_pushes_after_the_deadline() {
+        let members = vec![Member {
+            username: "alice".into(),
+            name: "Alice Smith".into(),
+        }];
+        let commit = |name: &str, email: &str, date: &str| Commit {
+            author_name: name.into(),
+            author_email: email.into(),
+            date: time(date),
+        };
+        let commits = vec![
+            commit("Alice Smith", "a@example.com", "2020-11-30T10:00:00+01:00"),
+            commit("Someone", "alice@example.com", "2020-12-01T10:00:00+01:00"),
+            commit("Bob", "bob@example.com", "2020-12-02T10:00:00+01:00"),
+        ];
+        let api = MockGitlab::new().with_event(
+            10,
+            Event {
+                action_name: Some("pushed to".into()),
+                author_username: Some("bob".into()),
+                created_at: time("2020-12-02T10:05:00+01:00"),
+                push_data: Some(PushData {
+                    commit_count: 1,
+                    ref_name: Some("master".into()),
+                }),
+            },
+        );
+        let events = api
+            .push_events(10, NaiveDate::from_ymd(2020, 11, 29))
+            .await
+            .unwrap();
+
+        let late = Late::new(
+            &members,
+            &BTreeMap::new(),
+            &commits,
+            Some(&events),
+            &time("2020-11-30T23:59:00+01:00"),
+        );
+
+        assert!(late.is_late());
+        assert_eq!(late.commits, 2);
+        assert_eq!(
+            late.authors.iter().collect::<Vec<_>>(),
+            vec!["Bob", "alice"]
+        );
+        assert_eq!(late.pushes.unwrap().count, 1);
+    }
+}
@@ -0,0 +1,75 @@
+//! `forked` manages exercises where every group works in its own fork of a gitlab project.
+//!
+//! The course is described by a [`Manifest`](config::Manifest) (`forked.yml`) that lists every
+//! [`Project`](config::Project) with its members. All requests to gitlab go through the
+//! [`GitlabApi`](gitlab::GitlabApi) trait, which is implemented by [`Gitlab`](gitlab::Gitlab) for
+//! a real instance and by [`MockGitlab`](gitlab::MockGitlab) for tests, and wrapped by
+//! [`DryRun`](gitlab::DryRun) for `--dry-run`. Commands that work on every project are executed
+//! concurrently by a [`Runner`](jobs::Runner).
+//!
+//! ```
+//! use forked::gitlab::MockGitlab;
+//! use forked::jobs::Runner;
+//! use std::time::Duration;
+//!
+//! # #[tokio::main]
+//! # async fn main() -> anyhow::Result<()> {
+//! let api = MockGitlab::new().with_fork(1, 10, "team-a", &["alice", "tutor"]);
+//! let runner = Runner::new(4, Duration::from_millis(0));
+//!
+//! let projects = forked::sync::discover(&api, &runner, 1, &["tutor".into()]).await?;
+//! assert_eq!(projects["team-a"].members[0].username, "alice");
+//! # Ok(())
+//! # }
+//! ```
+
+#![warn(missing_docs)]
+
+/// `forked.yml` and its projects
+pub mod config;
+/// Template context of feedback files
+pub mod context;
+/// Commits and changed lines of every member of a project
+pub mod contributions;
+/// Writing csv exports and reading csv rows
+mod csv;
+/// Errors of `forked`
+mod error;
+/// Creating and publishing feedback
+pub mod feedback;
+/// File system access that respects dry-run mode
+pub mod files;
+/// Git operations on the local clones
+pub mod git;
+/// Access to the gitlab api
+pub mod gitlab;
+/// Rubrics and scores of feedbacks
+pub mod grade;
+/// Points of every student on every graded feedback
+pub mod gradebook;
+/// Concurrent execution of per-project jobs
+pub mod jobs;
+/// Data types of the gitlab api
+pub mod json;
+/// Detection of late submissions
+pub mod late;
+/// Revoking and restoring write access at a deadline
+pub mod lock;
+/// Pushing upstream changes into all forks
+pub mod propagate;
+/// Creating forks and memberships from a roster
+pub mod provision;
+/// Isolation of commands that run student code
+pub mod sandbox;
+/// Detection of similar code across projects
+pub mod similarity;
+/// Snapshots of all projects at a point in time
+pub mod snapshot;
+/// State of the local clones
+pub mod status;
+/// Discovery of forks and synchronisation of `forked.yml`
+pub mod sync;
+/// Running the tests of every project
+pub mod testing;
+
+pub use error::Error;
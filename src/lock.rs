@@ -0,0 +1,330 @@
+use crate::config::Project;
+use crate::files;
+use crate::gitlab::GitlabApi;
+use crate::jobs::{Outcome, Runner};
+use crate::json::{AccessLevel, EditMemberRequest};
+use chrono::{DateTime, FixedOffset, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use tokio::time;
+
+/// The access levels members had before their project was locked, by project key.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Locked {
+    /// Locked members by project key
+    pub projects: BTreeMap<String, Vec<LockedMember>>,
+}
+
+/// A member whose access level was lowered by [`lock`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct LockedMember {
+    /// Username on gitlab
+    pub username: String,
+    /// Id of the user on gitlab
+    pub user_id: u32,
+    /// Access level before locking, which `unlock` restores
+    pub access_level: AccessLevel,
+}
+
+/// The members of one project whose access level was lowered.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Lock {
+    /// Locked members, including the ones recorded by earlier runs
+    pub members: Vec<LockedMember>,
+    /// Owners of the project, whose access level can't be lowered
+    pub owners: Vec<String>,
+}
+
+/// Locking a project failed after some members were already locked.
+#[derive(Debug, thiserror::Error)]
+#[error("{} (after locking {} member(s))", .error, .locked.len())]
+pub struct PartialLock {
+    /// Members that were locked before the error, which have to be recorded for `unlock`
+    pub locked: Vec<LockedMember>,
+    /// The error that stopped locking
+    pub error: anyhow::Error,
+}
+
+impl Locked {
+    /// Loads the state file, if it exists.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        if !path.exists() {
+            return Ok(Locked::default());
+        }
+
+        Ok(serde_yaml::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    /// Remembers the previous access levels of all locked members, also of projects where
+    /// locking failed halfway.
+    pub fn record(&mut self, outcomes: &[Outcome<Lock>]) {
+        for outcome in outcomes {
+            let members = match &outcome.result {
+                Ok(lock) => &lock.members,
+                Err(e) => match e.downcast_ref::<PartialLock>() {
+                    Some(partial) => &partial.locked,
+                    None => continue,
+                },
+            };
+            self.projects.insert(outcome.key.clone(), members.clone());
+        }
+    }
+
+    /// Forgets all successfully unlocked projects.
+    pub fn forget(&mut self, outcomes: &[Outcome<usize>]) {
+        for outcome in outcomes {
+            if outcome.result.is_ok() {
+                self.projects.remove(&outcome.key);
+            }
+        }
+    }
+
+    /// Writes the state file.
+    pub fn save(&self, path: &Path, dry_run: bool) -> anyhow::Result<()> {
+        files::write(path, &serde_yaml::to_string(self)?, dry_run)
+    }
+}
+
+/// Sleeps until `at`, returning immediately if it already passed.
+pub async fn wait_until(at: &DateTime<FixedOffset>) {
+    if let Ok(duration) = at.signed_duration_since(Utc::now()).to_std() {
+        println!("waiting until {}", at);
+        time::delay_for(duration).await;
+    }
+}
+
+/// Lowers the access level of all members listed in `forked.yml` to `access_level`.
+///
+/// Members that are already in `locked` are left alone, so that locking twice doesn't overwrite
+/// the recorded access levels, while a project where locking failed halfway is completed.
+pub async fn lock(
+    api: &dyn GitlabApi,
+    runner: &Runner,
+    projects: BTreeMap<String, Project>,
+    locked: &Locked,
+    access_level: AccessLevel,
+) -> Vec<Outcome<Lock>> {
+    runner
+        .run(projects, |key, project| {
+            let mut lock = Lock {
+                members: locked.projects.get(&key).cloned().unwrap_or_default(),
+                owners: Vec::new(),
+            };
+
+            async move {
+                match lock_members(api, &project, access_level, &mut lock).await {
+                    Ok(()) => Ok(lock),
+                    Err(error) if !lock.members.is_empty() => Err(PartialLock {
+                        locked: lock.members,
+                        error,
+                    }
+                    .into()),
+                    Err(error) => Err(error),
+                }
+            }
+        })
+        .await
+}
+
+async fn lock_members(
+    api: &dyn GitlabApi,
+    project: &Project,
+    access_level: AccessLevel,
+    lock: &mut Lock,
+) -> anyhow::Result<()> {
+    for member in api.members(project.id).await? {
+        if !project
+            .members
+            .iter()
+            .any(|student| student.username == member.username)
+            || lock
+                .members
+                .iter()
+                .any(|locked| locked.user_id == member.id)
+        {
+            continue;
+        }
+        let previous = match member.access_level {
+            Some(AccessLevel::Owner) => {
+                lock.owners.push(member.username);
+                continue;
+            }
+            Some(previous) if previous > access_level => previous,
+            _ => continue,
+        };
+
+        api.edit_member(project.id, member.id, &EditMemberRequest { access_level })
+            .await?;
+        lock.members.push(LockedMember {
+            username: member.username,
+            user_id: member.id,
+            access_level: previous,
+        });
+    }
+
+    Ok(())
+}
+
+/// Restores the access levels recorded in `locked` and returns the number of restored members.
+pub async fn unlock(
+    api: &dyn GitlabApi,
+    runner: &Runner,
+    projects: BTreeMap<String, Project>,
+    locked: &Locked,
+) -> Vec<Outcome<usize>> {
+    runner
+        .run(
+            projects
+                .into_iter()
+                .filter(|(key, _)| locked.projects.contains_key(key)),
+            |key, project| {
+                let members = locked.projects[&key].clone();
+
+                async move {
+                    for member in &members {
+                        let request = EditMemberRequest {
+                            access_level: member.access_level,
+                        };
+                        api.edit_member(project.id, member.user_id, &request)
+                            .await?;
+                    }
+                    Ok(members.len())
+                }
+            },
+        )
+        .await
+}
+
+impl fmt::Display for Lock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "locked {} member(s)", self.members.len())?;
+        if !self.owners.is_empty() {
+            write!(f, ", skipped owner(s) {}", self.owners.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gitlab::MockGitlab;
+    use crate::sync;
+    use tokio::time::Duration;
+
+    #[tokio::test]
+    async fn unlock_restores_what_lock_changed() {
+        let api = MockGitlab::new().with_fork(1, 10, "team-a", &["alice", "tutor"]);
+        let runner = Runner::new(4, Duration::from_millis(0));
+        let projects = || async {
+            sync::discover(&api, &runner, 1, &["tutor".to_string()])
+                .await
+                .unwrap()
+        };
+        let levels = || async {
+            api.members(10)
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|member| member.access_level.unwrap())
+                .collect::<Vec<_>>()
+        };
+        let mut locked = Locked::default();
+
+        let outcomes = lock(
+            &api,
+            &runner,
+            projects().await,
+            &locked,
+            AccessLevel::Reporter,
+        )
+        .await;
+        locked.record(&outcomes);
+        assert_eq!(
+            levels().await,
+            vec![AccessLevel::Reporter, AccessLevel::Developer]
+        );
+
+        let outcomes = lock(&api, &runner, projects().await, &locked, AccessLevel::Guest).await;
+        assert_eq!(
+            outcomes[0].result.as_ref().unwrap().members[0].access_level,
+            AccessLevel::Developer
+        );
+
+        let outcomes = unlock(&api, &runner, projects().await, &locked).await;
+        locked.forget(&outcomes);
+        assert_eq!(
+            levels().await,
+            vec![AccessLevel::Developer, AccessLevel::Developer]
+        );
+        assert!(locked.projects.is_empty());
+    }
+
+    #[tokio::test]
+    async fn lock_records_members_locked_before_a_failure() {
+        let api = MockGitlab::new()
+            .with_fork(1, 10, "team-a", &["alice", "bob", "carol"])
+            .with_owner(10, "carol")
+            .with_failing_member("bob");
+        let runner = Runner::new(4, Duration::from_millis(0));
+        let projects = || async { sync::discover(&api, &runner, 1, &[]).await.unwrap() };
+        let levels = || async {
+            api.members(10)
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|member| member.access_level.unwrap())
+                .collect::<Vec<_>>()
+        };
+        let mut locked = Locked::default();
+
+        let outcomes = lock(
+            &api,
+            &runner,
+            projects().await,
+            &locked,
+            AccessLevel::Reporter,
+        )
+        .await;
+        locked.record(&outcomes);
+        assert!(outcomes[0].result.is_err());
+        assert_eq!(locked.projects["team-a"].len(), 1);
+
+        let outcomes = lock(
+            &api,
+            &runner,
+            projects().await,
+            &locked,
+            AccessLevel::Reporter,
+        )
+        .await;
+        locked.record(&outcomes);
+        let lock = outcomes[0].result.as_ref().unwrap();
+        assert_eq!(lock.members.len(), 2);
+        assert_eq!(lock.owners, vec!["carol"]);
+        assert_eq!(
+            levels().await,
+            vec![
+                AccessLevel::Reporter,
+                AccessLevel::Reporter,
+                AccessLevel::Owner
+            ]
+        );
+
+        let outcomes = unlock(&api, &runner, projects().await, &locked).await;
+        locked.forget(&outcomes);
+        assert_eq!(
+            levels().await,
+            vec![
+                AccessLevel::Developer,
+                AccessLevel::Developer,
+                AccessLevel::Owner
+            ]
+        );
+    }
+}
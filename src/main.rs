@@ -1,23 +1,18 @@
 // use anyhow::Context;
+use chrono::{DateTime, FixedOffset};
 use clap::{ArgSettings, Clap};
-use config::*;
-use reqwest::{
-    header::{HeaderMap, HeaderValue},
-    Client,
+use forked::config::{Group, Manifest};
+use forked::gitlab::GitlabApi;
+use forked::json::AccessLevel;
+use forked::{
+    contributions, feedback, files, git, gitlab, grade, gradebook, jobs, late, lock, propagate,
+    provision, similarity, snapshot, status, sync, testing, Error,
 };
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use std::env;
 use std::fs;
 use std::path::PathBuf;
-use std::process::Command;
-use std::{thread, time};
-use thiserror::Error;
-use tinytemplate::TinyTemplate;
-
-mod config;
-mod json;
-
-const DELAY: time::Duration = time::Duration::from_secs(10);
+use tokio::time::Duration;
 
 #[derive(Clap, Debug)]
 #[clap(
@@ -29,9 +24,33 @@ pub struct Opts {
     /// Personal access token. (Not necessary if the environment variable `GITLAB_TOKEN` is set)
     #[clap(short='g', long, env = "GITLAB_TOKEN", setting = ArgSettings::HideEnvValues)]
     gitlab_token: String,
-    /// Gitlab api url. (Not necessary if the environment variable `GITLAB_API` is set)
+    /// Gitlab api url, e.g. `https://gitlab.com`, `https://gitlab.com/api` or `https://gitlab.com/api/v4`. (Not necessary if the environment variable `GITLAB_API` is set)
     #[clap(short='a', long, env = "GITLAB_API", setting = ArgSettings::HideEnvValues)]
     gitlab_api_url: String,
+    /// Maximum number of projects that are processed concurrently
+    #[clap(short = 'j', long, default_value = "4")]
+    jobs: usize,
+    /// Minimum delay between starting two jobs (in milliseconds)
+    #[clap(long, default_value = "0")]
+    delay: u64,
+    /// Number of retries of failed requests to gitlab [default: 5]
+    #[clap(long)]
+    retries: Option<u32>,
+    /// Delay before the first retry, doubled for every further retry (in milliseconds) [default: 1000]
+    #[clap(long)]
+    retry_delay: Option<u64>,
+    /// Maximum delay between two retries (in milliseconds) [default: 60000]
+    #[clap(long)]
+    max_retry_delay: Option<u64>,
+    /// Number of items requested per page from list endpoints of gitlab
+    #[clap(long, default_value = "100")]
+    per_page: u32,
+    /// Maximum number of pages requested from a list endpoint before failing
+    #[clap(long, default_value = "100")]
+    max_pages: u32,
+    /// Print the changes to gitlab and to the disk and the test commands instead of performing them
+    #[clap(long)]
+    dry_run: bool,
     #[clap(subcommand)]
     pub subcmd: SubCommand,
 }
@@ -42,12 +61,40 @@ pub enum FeedbackAction {
     Publish,
 }
 
+#[derive(Clap, Debug)]
+pub enum GradeAction {
+    Create,
+    Check,
+}
+
+#[derive(Clap, Debug)]
+pub enum Format {
+    Csv,
+    Json,
+}
+
+#[derive(Clap, Debug)]
+pub enum ReportFormat {
+    Markdown,
+    Html,
+}
+
 #[derive(Clap, Debug)]
 pub enum SubCommand {
-    /// Initialize a course, adding all forked repositories to `forked.yml`
+    /// Initialize a course, adding all forked repositories (or all projects of a group) to `forked.yml`
     Init {
         /// Id of the root repository
-        project_id: u32,
+        #[clap(required_unless_present = "group")]
+        project_id: Option<u32>,
+        /// Full path of a group with one project per team, used instead of the forks of a root repository (e.g. `course/ws20`)
+        #[clap(long, conflicts_with = "project-id")]
+        group: Option<String>,
+        /// Also add the projects of all subgroups of `--group`
+        #[clap(long, requires = "group")]
+        include_subgroups: bool,
+        /// Only add projects of `--group` whose name matches this regular expression (e.g. `^team-`)
+        #[clap(long, requires = "group")]
+        filter: Option<String>,
         /// Exclude members of forked projects (with username)
         #[clap(long)]
         exclude_members: Vec<String>,
@@ -61,14 +108,98 @@ pub enum SubCommand {
         #[clap(long, default_value = "feedbacks")]
         feedbacks_directory: PathBuf,
     },
+    /// Adds new forks (or projects of `Group`) to `forked.yml` and reports forks that disappeared or changed members
+    Sync {
+        /// Id of the root repository (defaults to `RootProjectId` in `forked.yml`, or to `Group` if that is missing)
+        project_id: Option<u32>,
+        /// Exclude members of forked projects (with username), in addition to `ExcludeMembers`
+        #[clap(long)]
+        exclude_members: Vec<String>,
+        /// Replace the members of existing projects with the members found on gitlab
+        #[clap(long)]
+        update_members: bool,
+        /// Remove projects that are no longer forks of the root repository
+        #[clap(long)]
+        prune: bool,
+    },
+    /// Forks the root repository for every team of a roster and adds the team members
+    Provision {
+        /// Path to the roster, one team per line followed by the usernames of its members (e.g. `Team A,alice,bob`), names with commas in quotes
+        #[clap(long)]
+        roster: PathBuf,
+        /// Full path of the group (or user) the forks are created in
+        #[clap(long)]
+        namespace: String,
+        /// Id of the root repository (defaults to `RootProjectId` in `forked.yml`)
+        #[clap(long)]
+        project_id: Option<u32>,
+        /// Access level of the members: guest, planner, reporter, developer or maintainer
+        #[clap(long, default_value = "developer")]
+        access_level: AccessLevel,
+    },
     /// Runs `git clone <repository>` for all groups
     Clone,
     /// Runs `git pull` for all groups
     Pull,
-    /// Runs `git checkout <branch>` for all groups
+    /// Runs `git checkout <branch>` for all groups, or checks out the state at `--before`
     Checkout {
         /// Name of the branch
+        #[clap(required_unless_present = "before")]
+        branch: Option<String>,
+        /// Check out the last commit on the default branch (`origin/HEAD`) before this time (e.g. `2020-11-30T23:59:00+01:00`)
+        #[clap(long, conflicts_with = "branch")]
+        before: Option<DateTime<FixedOffset>>,
+        /// Path to the report with the resolved commit of every project
+        #[clap(long, default_value = "snapshot.yml")]
+        report: PathBuf,
+    },
+    /// Pushes a branch of the root repository into every fork and opens a merge request
+    Propagate {
+        /// Branch of the root repository with the changes
+        #[clap(long)]
         branch: String,
+        /// Branch the changes are pushed to in every fork [default: upstream-<branch>]
+        #[clap(long)]
+        fork_branch: Option<String>,
+        /// Id of the root repository (defaults to `RootProjectId` in `forked.yml`)
+        #[clap(long)]
+        project_id: Option<u32>,
+    },
+    /// Lists all groups that committed or pushed after the deadline
+    Late {
+        /// Deadline of the exercise (e.g. `2020-11-30T23:59:00+01:00`)
+        #[clap(long)]
+        deadline: DateTime<FixedOffset>,
+        /// Only inspect the local clones, without querying the push events of gitlab
+        #[clap(long)]
+        local: bool,
+    },
+    /// Reports commits, changed lines and active days of every member, flagging inactive members
+    Contributions {
+        /// Only count commits authored after this time, e.g. the start of the exercise
+        #[clap(long)]
+        since: Option<DateTime<FixedOffset>>,
+    },
+    /// Lowers the access level of all members, recording the previous levels for `unlock`
+    Lock {
+        /// Wait until this time before locking (e.g. `2020-11-30T23:59:00+01:00`)
+        #[clap(long)]
+        at: Option<DateTime<FixedOffset>>,
+        /// Access level of the members after locking: guest or reporter
+        #[clap(long, default_value = "reporter")]
+        access_level: AccessLevel,
+        /// Path to the file with the previous access levels
+        #[clap(long, default_value = "locked.yml")]
+        state: PathBuf,
+    },
+    /// Restores the access levels recorded by `lock`
+    Unlock {
+        /// Wait until this time before unlocking (e.g. `2020-12-01T08:00:00+01:00`)
+        #[clap(long)]
+        at: Option<DateTime<FixedOffset>>,
+        /// Path to the file with the previous access levels
+        #[clap(long, default_value = "locked.yml")]
+        state: PathBuf,
     },
     /// Either create or publish all feedback files for all groups
     Feedback {
@@ -77,15 +208,80 @@ pub enum SubCommand {
         action: FeedbackAction,
         /// Name of the feedback template
         name: String,
+        /// Don't update published issues whose title and description did not change
+        #[clap(long)]
+        skip_unchanged: bool,
+    },
+    /// Either create empty score files from the rubric or check all score files against it
+    Grade {
+        /// Choose the action
+        #[clap(arg_enum)]
+        action: GradeAction,
+        /// Name of the feedback template with a `<name>.rubric.yml`
+        name: String,
+    },
+    /// Runs the tests of an exercise inside every clone and stores the results for the feedback
+    Test {
+        /// Name of the feedback the results are stored for
+        name: String,
+        /// Shell command that runs the tests (defaults to `Tests.<name>.Command` in `forked.yml`)
+        #[clap(long)]
+        cmd: Option<String>,
+        /// Seconds after which the tests are killed [default: 300]
+        #[clap(long)]
+        timeout: Option<u64>,
+        /// Path of a JUnit report written by the command, relative to the clone
+        #[clap(long)]
+        junit: Option<PathBuf>,
+        /// Run the tests in a sandbox without network and with a read-only clone (Linux only)
+        #[clap(long)]
+        sandbox: bool,
+        /// Allow network access inside the sandbox
+        #[clap(long, requires = "sandbox")]
+        allow_network: bool,
+        /// Maximum memory of every process inside the sandbox in MiB
+        #[clap(long, requires = "sandbox")]
+        memory_limit: Option<u64>,
+        /// Maximum cpu time of every process inside the sandbox in seconds
+        #[clap(long, requires = "sandbox")]
+        cpu_limit: Option<u64>,
+    },
+    /// Shows the branch, local changes and last commit of every clone
+    Status {
+        /// Also check whether the feedback file of this feedback exists
+        #[clap(long)]
+        feedback: Option<String>,
+        /// Print csv or json instead of a table
+        #[clap(long, arg_enum)]
+        format: Option<Format>,
+    },
+    /// Reports the most similar pairs of projects, ignoring code of the root repository
+    Similarity {
+        /// Files to compare, relative to every clone (e.g. `src/**/*.rs`)
+        #[clap(long)]
+        glob: String,
+        /// Local clone of the root repository, whose code is not counted as similar
+        #[clap(long)]
+        root: Option<PathBuf>,
+        /// Number of pairs in the report
+        #[clap(long, default_value = "10")]
+        top: usize,
+        /// Output format
+        #[clap(long, arg_enum, default_value = "markdown")]
+        format: ReportFormat,
+        /// Write the report to this file instead of printing it
+        #[clap(long)]
+        output: Option<PathBuf>,
+    },
+    /// Exports the points of every student on every graded feedback
+    Gradebook {
+        /// Output format
+        #[clap(long, arg_enum, default_value = "csv")]
+        format: Format,
+        /// Write the gradebook to this file instead of printing it
+        #[clap(long)]
+        output: Option<PathBuf>,
     },
-}
-
-#[derive(Copy, Clone, Debug, Error)]
-pub enum Error {
-    #[error("`username` in project {} is empty", project_id)]
-    MissingUsername { project_id: u32 },
-    #[error("`repository` in project {} is empty", project_id)]
-    MissingSsh { project_id: u32 },
 }
 
 #[tokio::main]
@@ -97,8 +293,8 @@ async fn main() -> anyhow::Result<()> {
     if let Err(e) = run(opts).await {
         if let Some(e) = e.downcast_ref::<Error>() {
             log::error!("{:?}", e);
-            eprintln!("{} (exit code: 1)", &e);
-            std::process::exit(1);
+            eprintln!("{} (exit code: {})", &e, e.exit_code());
+            std::process::exit(e.exit_code());
         } else {
             log::error!("{:?}", e);
             eprintln!("process didn't exit successfully: (exit code: 2)");
@@ -110,194 +306,560 @@ async fn main() -> anyhow::Result<()> {
 }
 
 async fn run(opts: Opts) -> Result<(), anyhow::Error> {
-    let mut headers = HeaderMap::new();
-    headers.insert("PRIVATE-TOKEN", HeaderValue::from_str(&opts.gitlab_token)?);
-
-    let client = Client::builder().default_headers(headers).build()?;
+    let dry_run = opts.dry_run;
+    let retry = Manifest::load()
+        .ok()
+        .and_then(|config| config.retry)
+        .unwrap_or_default();
+    let retry = gitlab::RetryPolicy {
+        max_retries: opts.retries.unwrap_or(retry.max_retries),
+        base_delay: opts.retry_delay.unwrap_or(retry.base_delay),
+        max_delay: opts.max_retry_delay.unwrap_or(retry.max_delay),
+    };
+
+    let gitlab = gitlab::Gitlab::new(&opts.gitlab_token, &opts.gitlab_api_url)?
+        .with_pagination(gitlab::Pagination {
+            per_page: opts.per_page,
+            max_pages: opts.max_pages,
+        })
+        .with_retry(retry);
+    // in dry-run mode, requests that change anything are only printed
+    let api: Box<dyn GitlabApi> = if dry_run {
+        Box::new(gitlab::DryRun::new(gitlab))
+    } else {
+        Box::new(gitlab)
+    };
+    let runner = jobs::Runner::new(opts.jobs, Duration::from_millis(opts.delay));
 
     match opts.subcmd {
         SubCommand::Init {
             project_id,
+            group,
+            include_subgroups,
+            filter,
             exclude_members,
             projects_directory,
             templates_directory,
             feedbacks_directory,
         } => {
-            let forks = json::Forks::get(&client, &opts.gitlab_api_url, project_id).await?;
-
-            thread::sleep(DELAY);
-
-            let mut projects = HashMap::new();
-
-            for fork in forks {
-                let members: Vec<Member> =
-                    json::Member::get(&client, &opts.gitlab_api_url, fork.id)
-                        .await?
-                        .into_iter()
-                        .map(|member| Member {
-                            username: member.username,
-                            name: member.name,
-                        })
-                        .filter(|member| !exclude_members.contains(&member.username))
-                        .collect();
-
-                if members.is_empty() {
-                    continue;
+            probe(&*api).await?;
+            let group = group.map(|path| Group {
+                path,
+                include_subgroups,
+                filter,
+            });
+            let projects = match (&group, project_id) {
+                (Some(group), _) => {
+                    sync::discover_group(&*api, &runner, group, &exclude_members).await?
                 }
-
-                projects.insert(
-                    fork.namespace.path,
-                    Project {
-                        id: fork.id as u32,
-                        members,
-                        repository: fork.ssh_url_to_repo,
-                    },
-                );
-
-                thread::sleep(DELAY);
-            }
+                (None, Some(project_id)) => {
+                    sync::discover(&*api, &runner, project_id, &exclude_members).await?
+                }
+                (None, None) => return Err(Error::MissingRootProject.into()),
+            };
 
             Manifest {
+                root_project_id: project_id,
+                group,
+                exclude_members,
+                aliases: BTreeMap::new(),
                 projects,
                 projects_directory,
                 templates_directory,
                 feedbacks_directory,
+                tests: BTreeMap::new(),
+                retry: None,
+                extra: BTreeMap::new(),
+            }
+            .save(dry_run)
+        }
+        SubCommand::Sync {
+            project_id,
+            exclude_members,
+            update_members,
+            prune,
+        } => {
+            let mut config = Manifest::load()?;
+            probe(&*api).await?;
+            let project_id = project_id.or(config.root_project_id);
+
+            let mut exclude = config.exclude_members.clone();
+            exclude.extend(exclude_members);
+
+            let projects = match (project_id, &config.group) {
+                // members are excluded by `merge`, which reports projects without other members
+                (Some(project_id), _) => sync::discover(&*api, &runner, project_id, &[]).await?,
+                (None, Some(group)) => sync::discover_group(&*api, &runner, group, &[]).await?,
+                (None, None) => return Err(Error::MissingRootProject.into()),
+            };
+            let changes = sync::merge(&mut config, projects, &exclude, update_members, prune);
+
+            if changes.is_empty() {
+                println!("`forked.yml` is up to date");
+            }
+            for change in &changes {
+                println!("{}", change);
+            }
+
+            config.root_project_id = project_id;
+            config.save(dry_run)
+        }
+        SubCommand::Provision {
+            roster,
+            namespace,
+            project_id,
+            access_level,
+        } => {
+            let mut config = if Manifest::exists() {
+                Manifest::load()?
+            } else {
+                Manifest::new(None)
+            };
+            let project_id = project_id
+                .or(config.root_project_id)
+                .ok_or(Error::MissingRootProject)?;
+            let teams = provision::parse_roster(&fs::read_to_string(roster)?)?;
+            probe(&*api).await?;
+
+            let outcomes = provision::provision(
+                &*api,
+                &runner,
+                project_id,
+                &namespace,
+                teams,
+                &config.projects,
+                access_level,
+            )
+            .await;
+
+            let failed = jobs::print_table(&outcomes, |project| format!("project {}", project.id));
+
+            for outcome in outcomes {
+                let project = match outcome.result {
+                    Ok(project) => project,
+                    Err(e) => match e.downcast::<provision::PartialProvision>() {
+                        Ok(partial) => partial.project,
+                        Err(_) => continue,
+                    },
+                };
+                config.projects.insert(outcome.key, project);
             }
-            .save()
+            config.root_project_id = Some(project_id);
+            config.save(dry_run)?;
+            succeeded(failed)
         }
         SubCommand::Clone => {
             let config = Manifest::load()?;
-            fs::create_dir_all(&config.projects_directory)?;
-
-            for (key, project) in config.projects {
-                print!("running `git clone {} {}` ... ", &project.repository, &key);
-
-                let output = Command::new("git")
-                    .args(&["clone", &project.repository, &key])
-                    .current_dir(&config.projects_directory)
-                    .output()?;
+            files::create_dir_all(&config.projects_directory, dry_run)?;
+            let projects_directory = &config.projects_directory;
 
-                log::debug!("{}", std::str::from_utf8(&output.stdout)?);
-                if output.status.success() {
-                    println!("\u{2713}");
-                } else {
-                    println!("\u{2715}");
-                    log::error!("{}", std::str::from_utf8(&output.stderr)?);
-                }
+            let outcomes = runner
+                .run(config.projects, |key, project| async move {
+                    git::clone(projects_directory, &project.repository, &key, dry_run).await
+                })
+                .await;
 
-                thread::sleep(DELAY);
-            }
-            Ok(())
+            let failed = jobs::print_table(&outcomes, |_| String::new());
+            succeeded(failed)
         }
         SubCommand::Pull => {
             let config = Manifest::load()?;
+            let projects_directory = &config.projects_directory;
 
-            for key in config.projects.keys() {
-                print!("running `git pull` for {} ... ", &key);
+            let outcomes = runner
+                .run(config.projects, |key, _| {
+                    let dir = projects_directory.join(&key);
+                    async move { git::pull(&dir, dry_run).await }
+                })
+                .await;
+
+            let failed = jobs::print_table(&outcomes, |_| String::new());
+            succeeded(failed)
+        }
+        SubCommand::Checkout {
+            branch: Some(branch),
+            ..
+        } => {
+            let config = Manifest::load()?;
+            let projects_directory = &config.projects_directory;
+
+            let outcomes = runner
+                .run(config.projects, |key, _| {
+                    let dir = projects_directory.join(&key);
+                    let branch = &branch;
+                    async move { git::checkout(&dir, branch, dry_run).await }
+                })
+                .await;
+
+            let failed = jobs::print_table(&outcomes, |_| String::new());
+            succeeded(failed)
+        }
+        SubCommand::Checkout {
+            before: Some(before),
+            report,
+            ..
+        } => {
+            let config = Manifest::load()?;
+            let projects_directory = &config.projects_directory;
 
-                let output = Command::new("git")
-                    .args(&["pull"])
-                    .current_dir(config.projects_directory.join(&key))
-                    .output()?;
+            let outcomes = runner
+                .run(config.projects, |key, _| {
+                    let dir = projects_directory.join(&key);
+                    let before = &before;
+                    async move { snapshot::checkout_before(&dir, before, dry_run).await }
+                })
+                .await;
 
-                log::debug!("{}", std::str::from_utf8(&output.stdout)?);
-                if output.status.success() {
-                    println!("\u{2713}");
-                } else {
-                    println!("\u{2715}");
-                    log::error!("{}", std::str::from_utf8(&output.stderr)?);
-                }
+            let failed = jobs::print_table(&outcomes, |sha| sha.clone());
 
-                thread::sleep(DELAY);
+            snapshot::Snapshot::new(before, outcomes).save(&report, dry_run)?;
+            succeeded(failed)
+        }
+        SubCommand::Checkout { .. } => unreachable!("clap requires either `branch` or `--before`"),
+        SubCommand::Propagate {
+            branch,
+            fork_branch,
+            project_id,
+        } => {
+            let config = Manifest::load()?;
+            let project_id = project_id
+                .or(config.root_project_id)
+                .ok_or(Error::MissingRootProject)?;
+            probe(&*api).await?;
+
+            let upstream = propagate::Upstream {
+                repository: api.project(project_id).await?.ssh_url_to_repo,
+                fork_branch: fork_branch.unwrap_or_else(|| format!("upstream-{}", branch)),
+                branch,
+            };
+            let outcomes = propagate::propagate(
+                &*api,
+                &runner,
+                config.projects,
+                &config.projects_directory,
+                &upstream,
+                dry_run,
+            )
+            .await?;
+
+            let failed = jobs::print_table(&outcomes, |propagation| propagation.to_string());
+            succeeded(failed)
+        }
+        SubCommand::Late { deadline, local } => {
+            let config = Manifest::load()?;
+            let projects_directory = &config.projects_directory;
+            let aliases = config.aliases;
+            if !local {
+                probe(&*api).await?;
             }
-            Ok(())
+            let api: Option<&dyn GitlabApi> = if local { None } else { Some(&*api) };
+
+            let outcomes = runner
+                .run(config.projects, |key, project| {
+                    let dir = projects_directory.join(&key);
+                    let deadline = &deadline;
+                    let aliases = &aliases;
+                    async move { late::check(api, &dir, &project, aliases, deadline).await }
+                })
+                .await;
+
+            let failed = jobs::print_table(&outcomes, |late| late.to_string());
+            succeeded(failed)
         }
-        SubCommand::Checkout { branch } => {
+        SubCommand::Contributions { since } => {
             let config = Manifest::load()?;
+            let projects_directory = &config.projects_directory;
+            let aliases = config.aliases;
+
+            let outcomes = runner
+                .run(config.projects, |key, project| {
+                    let dir = projects_directory.join(&key);
+                    let aliases = &aliases;
+                    let since = since.as_ref();
+                    async move { contributions::collect(&dir, &project, aliases, since).await }
+                })
+                .await;
+
+            let failed = jobs::print_table(&outcomes, |contributions| contributions.to_string());
+            let inactive: Vec<&str> = outcomes
+                .iter()
+                .filter(|outcome| {
+                    outcome
+                        .result
+                        .as_ref()
+                        .is_ok_and(|contributions| !contributions.inactive().is_empty())
+                })
+                .map(|outcome| outcome.key.as_str())
+                .collect();
+            if !inactive.is_empty() {
+                println!("projects with inactive members: {}", inactive.join(", "));
+            }
+            succeeded(failed)
+        }
+        SubCommand::Lock {
+            at,
+            access_level,
+            state,
+        } => {
+            let config = Manifest::load()?;
+            let mut locked = lock::Locked::load(&state)?;
+            probe(&*api).await?;
+            if let Some(at) = &at {
+                lock::wait_until(at).await;
+            }
 
-            for key in config.projects.keys() {
-                print!("running `git checkout {}` for {} ... ", branch, &key);
+            let outcomes = lock::lock(&*api, &runner, config.projects, &locked, access_level).await;
 
-                let output = Command::new("git")
-                    .args(&["checkout", &branch])
-                    .current_dir(config.projects_directory.join(&key))
-                    .output()?;
+            let failed = jobs::print_table(&outcomes, |lock| lock.to_string());
 
-                log::debug!("{}", std::str::from_utf8(&output.stdout)?);
-                if output.status.success() {
-                    println!("\u{2713}");
-                } else {
-                    println!("\u{2715}");
-                    log::error!("{}", std::str::from_utf8(&output.stderr)?);
-                }
+            locked.record(&outcomes);
+            locked.save(&state, dry_run)?;
+            succeeded(failed)
+        }
+        SubCommand::Unlock { at, state } => {
+            let config = Manifest::load()?;
+            let mut locked = lock::Locked::load(&state)?;
+            probe(&*api).await?;
+            if let Some(at) = &at {
+                lock::wait_until(at).await;
             }
 
-            Ok(())
+            let outcomes = lock::unlock(&*api, &runner, config.projects, &locked).await;
+
+            let failed =
+                jobs::print_table(&outcomes, |count| format!("unlocked {} member(s)", count));
+
+            locked.forget(&outcomes);
+            locked.save(&state, dry_run)?;
+            succeeded(failed)
         }
-        SubCommand::Feedback { name, action } => {
+        SubCommand::Feedback {
+            name,
+            action,
+            skip_unchanged,
+        } => {
             let config = Manifest::load()?;
 
-            match action {
+            let failed = match action {
                 FeedbackAction::Create => {
-                    let mut tt = TinyTemplate::new();
-                    let raw = fs::read_to_string(
-                        config.templates_directory.join(format!("{}.md", name)),
-                    )?;
-                    tt.add_template("Feedback", raw.as_str())?;
-
-                    fs::create_dir_all(&config.feedbacks_directory.join(&name))?;
-
-                    for (key, project) in config.projects {
-                        let rendered = tt.render("Feedback", &project)?;
-                        fs::write(
-                            config
-                                .feedbacks_directory
-                                .join(&name)
-                                .join(format!("{}.md", key)),
-                            rendered,
-                        )?;
-                    }
+                    let outcomes = feedback::create(&config, &name, dry_run).await?;
+                    jobs::print_table(&outcomes, |_| "created".to_string())
                 }
                 FeedbackAction::Publish => {
-                    for (key, project) in config.projects {
-                        print!("publishing issue for {} ... ", &key);
-
-                        let feedback_path = config
-                            .feedbacks_directory
-                            .join(&name)
-                            .join(format!("{}.md", key));
-
-                        let data = fs::read_to_string(feedback_path)?;
-
-                        let mut lines = data.lines();
-
-                        let title = lines
-                            .next()
-                            .unwrap_or("Feedback")
-                            .trim_matches('#')
-                            .trim()
-                            .to_string();
-
-                        let request = json::NewIssueRequest {
-                            title,
-                            description: lines.collect::<Vec<_>>().join("\n"),
-                            labels: vec!["feedback".into()],
-                        };
-
-                        if request
-                            .post(&client, &opts.gitlab_api_url, project.id)
-                            .await?
-                            .is_opened()
-                        {
-                            println!("\u{2713}");
-                        } else {
-                            println!("\u{2715}");
-                        }
-                        thread::sleep(DELAY);
-                    }
+                    probe(&*api).await?;
+                    let feedback_directory = config.feedbacks_directory.join(&name);
+                    let mut published = feedback::Published::load(&feedback_directory)?;
+                    let outcomes = feedback::publish(
+                        &*api,
+                        &runner,
+                        config.projects,
+                        &feedback_directory,
+                        &published,
+                        skip_unchanged,
+                    )
+                    .await;
+
+                    let failed =
+                        jobs::print_table(&outcomes, |publication| publication.to_string());
+
+                    published.record(&outcomes);
+                    published.save(&feedback_directory, dry_run)?;
+                    failed
+                }
+            };
+            succeeded(failed)
+        }
+        SubCommand::Grade { action, name } => {
+            let config = Manifest::load()?;
+
+            let failed = match action {
+                GradeAction::Create => {
+                    let outcomes = grade::create(&config, &name, dry_run)?;
+                    jobs::print_table(&outcomes, |&created| {
+                        if created { "created" } else { "already exists" }.to_string()
+                    })
+                }
+                GradeAction::Check => {
+                    let outcomes = grade::check(&config, &name)?;
+                    jobs::print_table(&outcomes, |grade| {
+                        format!("{}/{} points", grade.total, grade.max_total)
+                    })
+                }
+            };
+            succeeded(failed)
+        }
+        SubCommand::Gradebook { format, output } => {
+            let config = Manifest::load()?;
+            let gradebook = gradebook::Gradebook::new(&config)?;
+            for warning in &gradebook.warnings {
+                eprintln!("skipped {}", warning);
+            }
+
+            let contents = match format {
+                Format::Csv => gradebook.to_csv(),
+                Format::Json => serde_json::to_string_pretty(&gradebook)? + "\n",
+            };
+
+            match output {
+                Some(path) => files::write(&path, &contents, dry_run),
+                None => {
+                    print!("{}", contents);
+                    Ok(())
+                }
+            }
+        }
+        SubCommand::Status { feedback, format } => {
+            let config = Manifest::load()?;
+            let projects_directory = &config.projects_directory;
+            let feedback_directory = feedback
+                .as_ref()
+                .map(|name| config.feedbacks_directory.join(name));
+
+            let outcomes = runner
+                .run(config.projects, |key, _| {
+                    let dir = projects_directory.join(&key);
+                    let feedback = feedback_directory
+                        .as_ref()
+                        .map(|directory| directory.join(format!("{}.md", key)));
+                    async move { status::status(&dir, feedback.as_deref()).await }
+                })
+                .await;
+
+            match format {
+                None => {
+                    jobs::print_table(&outcomes, |status| status.to_string());
+                }
+                Some(Format::Csv) => print!("{}", status::to_csv(&outcomes)),
+                Some(Format::Json) => println!(
+                    "{}",
+                    serde_json::to_string_pretty(&status::rows(&outcomes))?
+                ),
+            }
+            succeeded(jobs::failed(&outcomes))
+        }
+        SubCommand::Similarity {
+            glob,
+            root,
+            top,
+            format,
+            output,
+        } => {
+            let config = Manifest::load()?;
+            let pattern = similarity::glob(&glob);
+            let ignored = match root {
+                Some(root) => {
+                    similarity::Submission::load("root", &root, &pattern, &Default::default())?
+                        .fingerprints
+                        .into_keys()
+                        .collect()
+                }
+                None => Default::default(),
+            };
+
+            let mut submissions = Vec::new();
+            let mut missing = Vec::new();
+            for key in config.projects.keys() {
+                let dir = config.projects_directory.join(key);
+                if dir.exists() {
+                    submissions.push(similarity::Submission::load(key, &dir, &pattern, &ignored)?);
+                } else {
+                    missing.push(key.clone());
                 }
             }
-            Ok(())
+
+            let mut pairs = similarity::compare(&submissions);
+            pairs.truncate(top);
+            let report = similarity::Report {
+                glob,
+                pairs,
+                missing,
+            };
+            let contents = match format {
+                ReportFormat::Markdown => report.to_markdown(),
+                ReportFormat::Html => report.to_html(),
+            };
+
+            match output {
+                Some(path) => files::write(&path, &contents, dry_run),
+                None => {
+                    print!("{}", contents);
+                    Ok(())
+                }
+            }
+        }
+        SubCommand::Test {
+            name,
+            cmd,
+            timeout,
+            junit,
+            sandbox,
+            allow_network,
+            memory_limit,
+            cpu_limit,
+        } => {
+            let config = Manifest::load()?;
+            let configured = config.tests.get(&name).cloned();
+            let mut isolation = configured.as_ref().and_then(|test| test.sandbox.clone());
+            if sandbox {
+                let isolation = isolation.get_or_insert_with(Default::default);
+                isolation.network |= allow_network;
+                isolation.memory = memory_limit.or(isolation.memory);
+                isolation.cpu = cpu_limit.or(isolation.cpu);
+            }
+            let test = testing::TestConfig {
+                command: cmd
+                    .or_else(|| configured.as_ref().map(|test| test.command.clone()))
+                    .ok_or_else(|| Error::MissingTestCommand { name: name.clone() })?,
+                timeout: timeout
+                    .or_else(|| configured.as_ref().map(|test| test.timeout))
+                    .unwrap_or_else(testing::default_timeout),
+                junit: junit.or_else(|| configured.and_then(|test| test.junit)),
+                sandbox: isolation,
+            };
+            let projects_directory = &config.projects_directory;
+            let feedbacks_directory = &config.feedbacks_directory;
+            let read_only = [
+                env::current_dir()?,
+                projects_directory.clone(),
+                config.templates_directory.clone(),
+                feedbacks_directory.clone(),
+            ];
+
+            let outcomes = runner
+                .run(config.projects, |key, _| {
+                    let dir = projects_directory.join(&key);
+                    let results = testing::results_path(feedbacks_directory, &name, &key);
+                    let (test, read_only) = (&test, &read_only);
+                    async move { testing::run(&dir, test, read_only, &results, dry_run).await }
+                })
+                .await;
+
+            let failed = jobs::print_table(&outcomes, |run| match run {
+                Some(run) => run.to_string(),
+                None => "not run".to_string(),
+            });
+            succeeded(failed)
         }
     }
 }
+
+/// Fails with [`Error::ProjectsFailed`] if the command failed for `failed` projects, after their
+/// errors were printed.
+fn succeeded(failed: usize) -> anyhow::Result<()> {
+    if failed > 0 {
+        Err(Error::ProjectsFailed { failed }.into())
+    } else {
+        Ok(())
+    }
+}
+
+/// Checks the gitlab api url and the token before the first real request.
+async fn probe(api: &dyn GitlabApi) -> anyhow::Result<()> {
+    let version = api.version().await?;
+    log::info!(
+        "connected to gitlab {} ({})",
+        version.version,
+        version.revision
+    );
+    Ok(())
+}
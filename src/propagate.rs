@@ -0,0 +1,260 @@
+use crate::config::Project;
+use crate::git;
+use crate::gitlab::GitlabApi;
+use crate::jobs::{Outcome, Runner};
+use crate::json::NewMergeRequest;
+use crate::Error;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+
+/// The upstream branch that is propagated and the branch it is pushed to in every fork.
+#[derive(Debug, Clone)]
+pub struct Upstream {
+    /// Url or path of the root repository
+    pub repository: String,
+    /// Branch of the root repository, e.g. `main`
+    pub branch: String,
+    /// Branch that is pushed to every fork and merged from
+    pub fork_branch: String,
+}
+
+/// What happened to a fork while propagating.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Propagation {
+    /// The fork already contains all upstream commits
+    UpToDate,
+    /// A merge request with the upstream commits was opened
+    Opened(u32),
+    /// The open merge request of an earlier run was updated with the upstream commits
+    Updated(u32),
+}
+
+impl fmt::Display for Propagation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Propagation::UpToDate => write!(f, "up to date"),
+            Propagation::Opened(iid) => write!(f, "opened merge request !{}", iid),
+            Propagation::Updated(iid) => write!(f, "updated merge request !{}", iid),
+        }
+    }
+}
+
+/// Pushes the `upstream` branch to a branch of every fork and opens a merge request against the
+/// default branch of the fork.
+///
+/// Forks whose default branch would conflict with the upstream commits fail with the conflicting
+/// files instead. If a merge request from the fork branch is still open, e.g. from an earlier
+/// run, the push updates it and no new one is opened.
+///
+/// Fails up front if git is older than 2.38, which is needed to find the conflicts.
+pub async fn propagate(
+    api: &dyn GitlabApi,
+    runner: &Runner,
+    projects: BTreeMap<String, Project>,
+    projects_directory: &Path,
+    upstream: &Upstream,
+    dry_run: bool,
+) -> anyhow::Result<Vec<Outcome<Propagation>>> {
+    let (major, minor) = git::version().await?;
+    if (major, minor) < (2, 38) {
+        return Err(Error::OutdatedGit {
+            command: "propagate".into(),
+            required: "2.38".into(),
+            found: format!("{}.{}", major, minor),
+        }
+        .into());
+    }
+
+    let Upstream {
+        repository,
+        branch,
+        fork_branch,
+    } = upstream;
+
+    let outcomes = runner
+        .run(projects, |key, project| {
+            let dir = projects_directory.join(&key);
+
+            async move {
+                git::git(&dir, &["fetch", "origin"]).await?;
+                git::git(&dir, &["fetch", repository, branch]).await?;
+                let sha = git::git(&dir, &["rev-parse", "FETCH_HEAD"])
+                    .await?
+                    .trim()
+                    .to_string();
+                let default_branch = git::default_branch(&dir).await?;
+
+                if git::is_ancestor(&dir, &sha, &default_branch).await? {
+                    return Ok(Propagation::UpToDate);
+                }
+
+                let conflicts = git::merge_conflicts(&dir, &default_branch, &sha).await?;
+                if !conflicts.is_empty() {
+                    return Err(anyhow::anyhow!(
+                        "{} conflicts with {} in {}",
+                        branch,
+                        default_branch,
+                        conflicts.join(", ")
+                    ));
+                }
+
+                let commits = git::git(
+                    &dir,
+                    &[
+                        "log",
+                        "--format=- %h %s",
+                        &format!("{}..{}", default_branch, sha),
+                    ],
+                )
+                .await?;
+
+                git::run(
+                    &dir,
+                    &[
+                        "push",
+                        "--force",
+                        "origin",
+                        &format!("{}:refs/heads/{}", sha, fork_branch),
+                    ],
+                    dry_run,
+                )
+                .await?;
+
+                let open = api.open_merge_requests(project.id, fork_branch).await?;
+                if let Some(merge_request) = open.first() {
+                    return Ok(Propagation::Updated(merge_request.iid));
+                }
+
+                let request = NewMergeRequest {
+                    source_branch: fork_branch.clone(),
+                    target_branch: default_branch.trim_start_matches("origin/").to_string(),
+                    title: format!("Update exercise from upstream {}", branch),
+                    description: format!(
+                        "Changes to the exercise since this fork was created:\n\n{}",
+                        commits
+                    ),
+                    remove_source_branch: true,
+                };
+                let merge_request = api.create_merge_request(project.id, &request).await?;
+
+                Ok(Propagation::Opened(merge_request.iid))
+            }
+        })
+        .await;
+
+    Ok(outcomes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::test_utils::{commit, git};
+    use crate::gitlab::MockGitlab;
+    use crate::sync;
+    use std::fs;
+    use tokio::time::Duration;
+
+    #[tokio::test]
+    async fn propagate_opens_merge_requests_or_reports_conflicts() {
+        let root = tempfile::tempdir().unwrap();
+        let upstream = root.path().join("upstream");
+        fs::create_dir(&upstream).unwrap();
+        git(&upstream, &["init", "-b", "master"]);
+        commit(&upstream, "sheet.md", "sheet 1");
+
+        let projects_directory = root.path().join("projects");
+        fs::create_dir(&projects_directory).unwrap();
+        for team in &["team-a", "team-b"] {
+            git(
+                root.path(),
+                &["clone", "--bare", "upstream", &format!("{}.git", team)],
+            );
+            git(
+                &projects_directory,
+                &["clone", &format!("../{}.git", team), team],
+            );
+        }
+        let team_b = projects_directory.join("team-b");
+        commit(&team_b, "sheet.md", "solution of team b");
+        git(&team_b, &["push", "origin", "master"]);
+
+        commit(&upstream, "sheet.md", "sheet 1 with a fix");
+        commit(&upstream, "hints.md", "hints");
+
+        let api = MockGitlab::new()
+            .with_fork(1, 10, "team-a", &["alice"])
+            .with_fork(1, 11, "team-b", &["bob"]);
+        let runner = Runner::new(4, Duration::from_millis(0));
+        let projects = sync::discover(&api, &runner, 1, &[]).await.unwrap();
+
+        let upstream = Upstream {
+            repository: upstream.to_str().unwrap().to_string(),
+            branch: "master".into(),
+            fork_branch: "upstream-master".into(),
+        };
+        let outcomes = propagate(
+            &api,
+            &runner,
+            projects,
+            &projects_directory,
+            &upstream,
+            false,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            outcomes[0].result.as_ref().unwrap(),
+            &Propagation::Opened(1)
+        );
+        let merge_requests = api.merge_requests(10);
+        assert_eq!(merge_requests[0].source_branch, "upstream-master");
+        assert_eq!(merge_requests[0].target_branch, "master");
+        assert_eq!(merge_requests[0].description.matches("\n- ").count(), 2);
+
+        assert_eq!(
+            outcomes[1].result.as_ref().unwrap_err().to_string(),
+            "master conflicts with origin/master in sheet.md"
+        );
+        assert!(api.merge_requests(11).is_empty());
+
+        // rerun with another upstream commit while the merge request is still open
+        commit(Path::new(&upstream.repository), "tests.md", "tests");
+        let projects = sync::discover(&api, &runner, 1, &[]).await.unwrap();
+        let outcomes = propagate(
+            &api,
+            &runner,
+            projects,
+            &projects_directory,
+            &upstream,
+            false,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            outcomes[0].result.as_ref().unwrap(),
+            &Propagation::Updated(1)
+        );
+        assert_eq!(api.merge_requests(10).len(), 1);
+
+        let projects = sync::discover(&api, &runner, 1, &[]).await.unwrap();
+        git(&projects_directory.join("team-a"), &["pull"]);
+        git(
+            &projects_directory.join("team-a"),
+            &["merge", "origin/upstream-master"],
+        );
+        git(&projects_directory.join("team-a"), &["push"]);
+        let outcomes = propagate(
+            &api,
+            &runner,
+            projects,
+            &projects_directory,
+            &upstream,
+            false,
+        )
+        .await
+        .unwrap();
+        assert_eq!(outcomes[0].result.as_ref().unwrap(), &Propagation::UpToDate);
+    }
+}
@@ -0,0 +1,273 @@
+use crate::config::{Member, Project};
+use crate::csv;
+use crate::gitlab::GitlabApi;
+use crate::jobs::{Outcome, Runner};
+use crate::json::{AccessLevel, NewForkRequest, NewMemberRequest};
+use crate::Error;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// A team of the roster, which works in its own fork.
+#[derive(Debug, PartialEq)]
+pub struct Team {
+    /// Name as written in the roster, e.g. `Team A`
+    pub name: String,
+    /// Usernames of the members, without a leading `@`
+    pub usernames: Vec<String>,
+}
+
+impl Team {
+    /// The path of the fork and the key in `forked.yml`, e.g. `team-a` for `Team A`.
+    pub fn key(&self) -> String {
+        let mut key = String::new();
+        for c in self.name.trim().to_lowercase().chars() {
+            if c.is_ascii_alphanumeric() || c == '_' || c == '.' {
+                key.push(c);
+            } else if !key.ends_with('-') {
+                key.push('-');
+            }
+        }
+        key.trim_matches('-').to_string()
+    }
+}
+
+/// Parses a roster with one team per line: the name of the team followed by the usernames of
+/// its members, separated by commas (e.g. `Team A,alice,bob`). Cells with commas are quoted as
+/// in csv (e.g. `"Smith, Jones",alice`).
+///
+/// Empty lines, lines starting with `#` and a header line starting with `team` are skipped.
+pub fn parse_roster(data: &str) -> Result<Vec<Team>, Error> {
+    let mut teams = Vec::new();
+    let mut keys = BTreeSet::new();
+
+    for (index, line) in data.lines().enumerate() {
+        let invalid = |message: String| Error::InvalidRoster {
+            line: index + 1,
+            message,
+        };
+
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let cells = csv::parse_row(line).map_err(invalid)?;
+        let mut cells = cells.into_iter();
+        let name = cells.next().unwrap_or_default();
+        if index == 0 && name.eq_ignore_ascii_case("team") {
+            continue;
+        }
+
+        let team = Team {
+            name,
+            usernames: cells
+                .map(|username| username.trim().trim_start_matches('@').to_string())
+                .filter(|username| !username.is_empty())
+                .collect(),
+        };
+
+        let key = team.key();
+        if key.is_empty() {
+            return Err(invalid(format!("`{}` is not a valid team name", team.name)));
+        }
+        if team.usernames.is_empty() {
+            return Err(invalid(format!("team `{}` has no members", team.name)));
+        }
+        if !keys.insert(key.clone()) {
+            return Err(invalid(format!("team `{}` is listed twice", key)));
+        }
+
+        teams.push(team);
+    }
+
+    Ok(teams)
+}
+
+/// Provisioning a team failed after its fork was created.
+#[derive(Debug, thiserror::Error)]
+#[error("{} (project {} was created with {} member(s))", .error, .project.id, .project.members.len())]
+pub struct PartialProvision {
+    /// The fork with the members added before the error, which has to be written to `forked.yml`
+    pub project: Project,
+    /// The error that stopped provisioning
+    pub error: anyhow::Error,
+}
+
+/// Forks `root_project_id` into `namespace` once per team and adds its members with
+/// `access_level`.
+///
+/// All users are looked up before forking, so that an unknown username doesn't leave a fork
+/// behind. Teams that already have a project in `existing` aren't forked again, only their
+/// missing members are added.
+pub async fn provision(
+    api: &dyn GitlabApi,
+    runner: &Runner,
+    root_project_id: u32,
+    namespace: &str,
+    teams: Vec<Team>,
+    existing: &BTreeMap<String, Project>,
+    access_level: AccessLevel,
+) -> Vec<Outcome<Project>> {
+    runner
+        .run(
+            teams.into_iter().map(|team| (team.key(), team)),
+            |key, team| async move {
+                let mut users = Vec::new();
+                for username in &team.usernames {
+                    users.push(api.user(username).await?);
+                }
+
+                let mut project = match existing.get(&key) {
+                    Some(project) => project.clone(),
+                    None => {
+                        let request = NewForkRequest {
+                            namespace_path: namespace.to_string(),
+                            path: key,
+                            name: team.name,
+                        };
+                        let fork = api.fork(root_project_id, &request).await?;
+                        Project {
+                            id: fork.id,
+                            members: Vec::new(),
+                            repository: fork.ssh_url_to_repo,
+                            web_url: fork.web_url,
+                            extra: BTreeMap::new(),
+                        }
+                    }
+                };
+
+                for user in users {
+                    if project
+                        .members
+                        .iter()
+                        .any(|member| member.username == user.username)
+                    {
+                        continue;
+                    }
+
+                    let request = NewMemberRequest {
+                        user_id: user.id,
+                        access_level,
+                    };
+                    if let Err(error) = api.add_member(project.id, &request).await {
+                        return Err(PartialProvision { project, error }.into());
+                    }
+                    project.members.push(Member {
+                        username: user.username,
+                        name: user.name,
+                    });
+                }
+
+                Ok(project)
+            },
+        )
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gitlab::MockGitlab;
+    use tokio::time::Duration;
+
+    #[test]
+    fn parse_roster_skips_header_and_comments() {
+        let teams =
+            parse_roster("team,members\n# late registrations\n\nTeam A, alice, @bob\n").unwrap();
+
+        assert_eq!(
+            teams,
+            vec![Team {
+                name: "Team A".into(),
+                usernames: vec!["alice".into(), "bob".into()],
+            }]
+        );
+        assert_eq!(teams[0].key(), "team-a");
+    }
+
+    #[test]
+    fn parse_roster_rejects_duplicate_and_empty_teams() {
+        let error = parse_roster("team-a,alice\nTeam A,bob").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "line 2 of the roster is invalid (team `team-a` is listed twice)"
+        );
+        assert!(parse_roster("team-a").is_err());
+    }
+
+    #[test]
+    fn parse_roster_reads_quoted_names() {
+        let teams = parse_roster("\"Smith, Jones\",alice\n\"Team \"\"B\"\"\", bob").unwrap();
+        assert_eq!(teams[0].name, "Smith, Jones");
+        assert_eq!(teams[0].usernames, vec!["alice"]);
+        assert_eq!(teams[1].key(), "team-b");
+
+        let error = parse_roster("\"Smith, Jones,alice").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "line 1 of the roster is invalid (a quoted cell is not closed)"
+        );
+    }
+
+    #[tokio::test]
+    async fn provision_forks_and_adds_members() {
+        let api = MockGitlab::new().with_user("alice").with_user("bob");
+        let runner = Runner::new(4, Duration::from_millis(0));
+        let teams = parse_roster("team-a,alice,bob\nteam-b,carol").unwrap();
+
+        let outcomes = provision(
+            &api,
+            &runner,
+            1,
+            "course",
+            teams,
+            &BTreeMap::new(),
+            AccessLevel::Developer,
+        )
+        .await;
+
+        let project = outcomes[0].result.as_ref().unwrap();
+        assert_eq!(project.repository, "git@example.com:course/team-a.git");
+        assert_eq!(project.members.len(), 2);
+        assert_eq!(api.members(project.id).await.unwrap().len(), 2);
+        // carol is unknown, so team-b isn't forked at all
+        assert!(outcomes[1].result.is_err());
+        assert_eq!(api.forks_of(1).len(), 1);
+        // one fork and two members
+        assert_eq!(api.mutations(), 3);
+    }
+
+    #[tokio::test]
+    async fn provision_keeps_the_fork_when_adding_members_fails() {
+        let api = MockGitlab::new()
+            .with_user("alice")
+            .with_user("bob")
+            .with_failing_member("bob");
+        let runner = Runner::new(4, Duration::from_millis(0));
+        let teams = || parse_roster("team-a,alice,bob").unwrap();
+        let provision = |existing| {
+            provision(
+                &api,
+                &runner,
+                1,
+                "course",
+                teams(),
+                existing,
+                AccessLevel::Developer,
+            )
+        };
+
+        let none = BTreeMap::new();
+        let outcomes = provision(&none).await;
+        let error = outcomes[0].result.as_ref().unwrap_err();
+        let project = &error.downcast_ref::<PartialProvision>().unwrap().project;
+        assert_eq!(project.members.len(), 1);
+
+        let existing = vec![("team-a".to_string(), project.clone())]
+            .into_iter()
+            .collect();
+        let outcomes = provision(&existing).await;
+        assert_eq!(outcomes[0].result.as_ref().unwrap().members.len(), 2);
+        assert_eq!(api.members(project.id).await.unwrap().len(), 2);
+        assert_eq!(api.forks_of(1).len(), 1);
+    }
+}
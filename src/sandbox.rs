@@ -0,0 +1,299 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+
+/// Isolation of commands that run student code, configured per feedback in `forked.yml`.
+///
+/// On Linux, the command runs in its own user and mount namespace, where the clone, the home
+/// directory and the directories of `forked` are mounted read-only, and without network unless
+/// `Network` is set. `/tmp` stays writable, e.g. for `CARGO_TARGET_DIR=/tmp/target`, and so do
+/// other directories the user may write to. There is no pid namespace, so the command can still
+/// see and signal the other processes of the user.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Sandbox {
+    /// Allow access to the network
+    #[serde(default)]
+    pub network: bool,
+    /// Maximum size of the address space of every process in MiB
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory: Option<u64>,
+    /// Maximum cpu time of every process in seconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpu: Option<u64>,
+    /// Maximum size of a written file in MiB
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_size: Option<u64>,
+}
+
+impl Sandbox {
+    /// Isolates `command`, which runs inside the clone `dir`, and mounts `read_only` read-only
+    /// as well, skipping directories that don't exist.
+    #[cfg(target_os = "linux")]
+    pub fn apply(
+        &self,
+        command: &mut Command,
+        dir: &Path,
+        read_only: &[PathBuf],
+    ) -> anyhow::Result<()> {
+        linux::apply(self, command, dir, read_only)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn apply(
+        &self,
+        _command: &mut Command,
+        _dir: &Path,
+        _read_only: &[PathBuf],
+    ) -> anyhow::Result<()> {
+        Err(anyhow::anyhow!("sandboxing is only supported on linux"))
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use super::Sandbox;
+    use std::env;
+    use std::ffi::CString;
+    use std::io;
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::{Path, PathBuf};
+    use std::ptr;
+    use tokio::process::Command;
+
+    const MIB: u64 = 1024 * 1024;
+
+    #[cfg(target_env = "gnu")]
+    type Resource = libc::__rlimit_resource_t;
+    #[cfg(not(target_env = "gnu"))]
+    type Resource = libc::c_int;
+
+    /// Not defined by `libc` for every target
+    const ST_RELATIME: libc::c_ulong = 4096;
+
+    pub fn apply(
+        sandbox: &Sandbox,
+        command: &mut Command,
+        dir: &Path,
+        read_only: &[PathBuf],
+    ) -> anyhow::Result<()> {
+        let dir = dir.canonicalize()?;
+        let mut paths = Vec::new();
+        if let Some(home) = env::var_os("HOME") {
+            paths.push(CString::new(home.as_bytes())?);
+        }
+        for path in read_only.iter().filter(|path| path.exists()) {
+            paths.push(CString::new(path.canonicalize()?.as_os_str().as_bytes())?);
+        }
+        paths.push(CString::new(dir.as_os_str().as_bytes())?);
+        let dir = CString::new(dir.as_os_str().as_bytes())?;
+
+        let mut read_only = Vec::new();
+        for path in paths {
+            let flags = mount_flags(&path)?;
+            read_only.push((path, flags));
+        }
+
+        // the maps are prepared here, since the closure below must not allocate
+        let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+        let uid_map = format!("{} {} 1", uid, uid);
+        let gid_map = format!("{} {} 1", gid, gid);
+
+        let mut namespaces = libc::CLONE_NEWUSER | libc::CLONE_NEWNS;
+        if !sandbox.network {
+            namespaces |= libc::CLONE_NEWNET;
+        }
+
+        let limits: Vec<(Resource, u64)> = vec![
+            (libc::RLIMIT_AS, sandbox.memory.map(|memory| memory * MIB)),
+            (libc::RLIMIT_CPU, sandbox.cpu),
+            (libc::RLIMIT_FSIZE, sandbox.file_size.map(|size| size * MIB)),
+        ]
+        .into_iter()
+        .filter_map(|(resource, limit)| Some((resource, limit?)))
+        .collect();
+
+        // runs in the forked child, right before the command is executed
+        unsafe {
+            command.pre_exec(move || {
+                check(libc::unshare(namespaces))?;
+                write(b"/proc/self/setgroups\0", b"deny")?;
+                write(b"/proc/self/uid_map\0", uid_map.as_bytes())?;
+                write(b"/proc/self/gid_map\0", gid_map.as_bytes())?;
+
+                let root = b"/\0".as_ptr().cast();
+                check(libc::mount(
+                    ptr::null(),
+                    root,
+                    ptr::null(),
+                    libc::MS_REC | libc::MS_PRIVATE,
+                    ptr::null(),
+                ))?;
+                for (path, flags) in &read_only {
+                    check(libc::mount(
+                        path.as_ptr(),
+                        path.as_ptr(),
+                        ptr::null(),
+                        libc::MS_BIND | libc::MS_REC,
+                        ptr::null(),
+                    ))?;
+                    check(libc::mount(
+                        ptr::null(),
+                        path.as_ptr(),
+                        ptr::null(),
+                        libc::MS_BIND | libc::MS_REMOUNT | libc::MS_RDONLY | flags,
+                        ptr::null(),
+                    ))?;
+                }
+                // the working directory still points into the writable mount
+                check(libc::chdir(dir.as_ptr()))?;
+
+                for (resource, limit) in &limits {
+                    let limit = libc::rlimit {
+                        rlim_cur: *limit,
+                        rlim_max: *limit,
+                    };
+                    check(libc::setrlimit(*resource, &limit))?;
+                }
+                Ok(())
+            });
+        }
+        Ok(())
+    }
+
+    /// The flags of the mount containing `path` that a remount has to repeat, since they are
+    /// locked inside a user namespace, e.g. `nosuid` of `/dev/shm`.
+    fn mount_flags(path: &CString) -> io::Result<libc::c_ulong> {
+        let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+        check(unsafe { libc::statvfs(path.as_ptr(), &mut stat) })?;
+
+        let mut flags = 0;
+        for (st, ms) in &[
+            (libc::ST_NOSUID, libc::MS_NOSUID),
+            (libc::ST_NODEV, libc::MS_NODEV),
+            (libc::ST_NOEXEC, libc::MS_NOEXEC),
+            (libc::ST_NODIRATIME, libc::MS_NODIRATIME),
+        ] {
+            if stat.f_flag & st != 0 {
+                flags |= ms;
+            }
+        }
+        // the atime mode is locked as well and reset to `relatime` unless it is given
+        flags |= if stat.f_flag & libc::ST_NOATIME != 0 {
+            libc::MS_NOATIME
+        } else if stat.f_flag & ST_RELATIME != 0 {
+            libc::MS_RELATIME
+        } else {
+            libc::MS_STRICTATIME
+        };
+        Ok(flags)
+    }
+
+    fn check(result: libc::c_int) -> io::Result<()> {
+        if result == -1 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Writes `data` to the nul-terminated `path` without allocating.
+    fn write(path: &[u8], data: &[u8]) -> io::Result<()> {
+        unsafe {
+            let fd = libc::open(path.as_ptr().cast(), libc::O_WRONLY);
+            check(fd)?;
+            let written = libc::write(fd, data.as_ptr().cast(), data.len());
+            libc::close(fd);
+            if written == -1 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::io;
+    use std::process::Output;
+
+    async fn run(sandbox: &Sandbox, dir: &Path, script: &str) -> io::Result<Output> {
+        run_protecting(sandbox, dir, &[], script).await
+    }
+
+    async fn run_protecting(
+        sandbox: &Sandbox,
+        dir: &Path,
+        read_only: &[PathBuf],
+        script: &str,
+    ) -> io::Result<Output> {
+        let mut command = Command::new("sh");
+        command.args(["-c", script]).current_dir(dir);
+        sandbox.apply(&mut command, dir, read_only).unwrap();
+        command.output().await
+    }
+
+    /// Whether unprivileged user namespaces are enabled, checked without the rest of the sandbox.
+    fn user_namespaces_enabled() -> bool {
+        use std::os::unix::process::CommandExt;
+
+        let mut command = std::process::Command::new("true");
+        unsafe {
+            command.pre_exec(|| match libc::unshare(libc::CLONE_NEWUSER) {
+                -1 => Err(io::Error::last_os_error()),
+                _ => Ok(()),
+            });
+        }
+        command.status().is_ok_and(|status| status.success())
+    }
+
+    #[tokio::test]
+    async fn sandbox_protects_the_clone_and_the_network() {
+        if !user_namespaces_enabled() {
+            return;
+        }
+
+        let directory = tempfile::tempdir().unwrap();
+        fs::write(directory.path().join("main.rs"), "fn main() {}").unwrap();
+
+        // mounts like `/dev/shm` are usually `nosuid` and `nodev`, which the remount has to keep
+        if Path::new("/dev/shm").is_dir() {
+            let shm = tempfile::tempdir_in("/dev/shm").unwrap();
+            run(&Sandbox::default(), shm.path(), "true").await.unwrap();
+        }
+
+        let output = run(
+            &Sandbox::default(),
+            directory.path(),
+            "rm main.rs; wc -l < /proc/net/dev",
+        )
+        .await
+        .unwrap();
+        assert!(directory.path().join("main.rs").exists());
+        // two header lines and the loopback interface
+        assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "3");
+
+        let feedbacks = tempfile::tempdir().unwrap();
+        let script = format!("touch {}/grade.yml", feedbacks.path().display());
+        let read_only = [feedbacks.path().to_path_buf(), PathBuf::from("missing")];
+        run_protecting(&Sandbox::default(), directory.path(), &read_only, &script)
+            .await
+            .unwrap();
+        assert!(!feedbacks.path().join("grade.yml").exists());
+
+        let sandbox = Sandbox {
+            file_size: Some(1),
+            ..Sandbox::default()
+        };
+        let output = run(
+            &sandbox,
+            directory.path(),
+            "head -c 2000000 /dev/zero > /tmp/forked-sandbox-test; echo $?; rm -f /tmp/forked-sandbox-test",
+        )
+        .await
+        .unwrap();
+        assert_ne!(String::from_utf8_lossy(&output.stdout).trim(), "0");
+    }
+}
@@ -0,0 +1,574 @@
+use regex::Regex;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+
+/// Number of tokens hashed into one fingerprint; shorter matches are not detected.
+const K: usize = 12;
+/// Window of the winnowing algorithm; matches of at least `K + W - 1` tokens are always detected.
+const W: usize = 8;
+
+/// Identifiers that are kept as they are, all other identifiers are treated as equal so that
+/// renaming variables doesn't hide a copy.
+const KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "case", "catch", "class", "const", "continue", "def", "do",
+    "else", "enum", "extends", "false", "fn", "for", "if", "impl", "import", "in", "let", "loop",
+    "match", "mod", "mut", "new", "null", "pub", "return", "self", "static", "struct", "switch",
+    "trait", "true", "try", "type", "use", "var", "void", "where", "while",
+];
+
+/// Converts a glob like `src/**/*.rs` or `*.{c,h}` into a regex matching paths relative to a
+/// clone, with `/` as separator.
+pub fn glob(pattern: &str) -> Regex {
+    let mut regex = String::from("^");
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    regex.push_str("(?:.*/)?");
+                } else {
+                    regex.push_str(".*");
+                }
+            }
+            '*' => regex.push_str("[^/]*"),
+            '?' => regex.push_str("[^/]"),
+            '{' => regex.push_str("(?:"),
+            '}' => regex.push(')'),
+            ',' => regex.push('|'),
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    regex.push('$');
+
+    Regex::new(&regex).unwrap_or_else(|_| {
+        // unbalanced braces, match them literally
+        Regex::new(&format!("^{}$", regex::escape(pattern))).expect("valid regex")
+    })
+}
+
+/// A token of source code with the line it starts on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    /// The normalized token, e.g. `I` for any identifier
+    pub text: String,
+    /// Line number, starting at 1
+    pub line: usize,
+}
+
+/// Splits source code into tokens, skipping whitespace and comments.
+///
+/// Identifiers other than keywords of common languages (e.g. `if` or `return`) become `I`,
+/// numbers `N` and string literals `S`.
+pub fn tokenize(source: &str) -> Vec<Token> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut line = 1;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let start = line;
+        let text = if c == '\n' {
+            line += 1;
+            i += 1;
+            continue;
+        } else if c.is_whitespace() {
+            i += 1;
+            continue;
+        } else if c == '/' && chars.get(i + 1) == Some(&'/') {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+            continue;
+        } else if c == '/' && chars.get(i + 1) == Some(&'*') {
+            i += 2;
+            while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                if chars[i] == '\n' {
+                    line += 1;
+                }
+                i += 1;
+            }
+            i += 2;
+            continue;
+        } else if c.is_alphabetic() || c == '_' {
+            let end = scan(&chars, i, |c| c.is_alphanumeric() || c == '_');
+            let word: String = chars[i..end].iter().collect();
+            i = end;
+            if KEYWORDS.contains(&word.as_str()) {
+                word
+            } else {
+                "I".into()
+            }
+        } else if c.is_ascii_digit() {
+            i = scan(&chars, i, |c| c.is_alphanumeric() || c == '_' || c == '.');
+            "N".into()
+        } else if c == '"' {
+            i += 1;
+            while i < chars.len() && chars[i] != '"' {
+                match chars[i] {
+                    '\\' => i += 1,
+                    '\n' => line += 1,
+                    _ => {}
+                }
+                i += 1;
+            }
+            i += 1;
+            "S".into()
+        } else {
+            i += 1;
+            c.to_string()
+        };
+
+        tokens.push(Token { text, line: start });
+    }
+
+    tokens
+}
+
+fn scan(chars: &[char], start: usize, matches: impl Fn(char) -> bool) -> usize {
+    let mut end = start;
+    while end < chars.len() && matches(chars[end]) {
+        end += 1;
+    }
+    end
+}
+
+/// A fingerprint of `K` tokens with the lines they span.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Fingerprint {
+    /// Hash of the tokens
+    pub hash: u64,
+    /// First and last line of the tokens
+    pub lines: (usize, usize),
+}
+
+/// Selects fingerprints of the tokens with winnowing: the smallest hash of every window of `W`
+/// consecutive k-grams.
+pub fn fingerprints(tokens: &[Token]) -> Vec<Fingerprint> {
+    if tokens.len() < K {
+        return Vec::new();
+    }
+
+    let grams: Vec<Fingerprint> = tokens
+        .windows(K)
+        .map(|gram| {
+            let mut hasher = DefaultHasher::new();
+            for token in gram {
+                token.text.hash(&mut hasher);
+            }
+            Fingerprint {
+                hash: hasher.finish(),
+                lines: (gram[0].line, gram[K - 1].line),
+            }
+        })
+        .collect();
+
+    let mut selected: Vec<Fingerprint> = Vec::new();
+    let mut last = None;
+    for (offset, window) in grams.windows(W.min(grams.len())).enumerate() {
+        // the rightmost minimum, so that equal hashes in a row are only selected once
+        let (position, fingerprint) = window
+            .iter()
+            .enumerate()
+            .rev()
+            .min_by_key(|(_, fingerprint)| fingerprint.hash)
+            .expect("windows are not empty");
+        if last != Some(offset + position) {
+            last = Some(offset + position);
+            selected.push(*fingerprint);
+        }
+    }
+
+    selected
+}
+
+/// Where a fingerprint occurs in a submission.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Location {
+    /// Path relative to the clone
+    pub file: PathBuf,
+    /// First and last line
+    pub lines: (usize, usize),
+}
+
+/// The fingerprints of the files of one clone.
+#[derive(Debug)]
+pub struct Submission {
+    /// Key of the project
+    pub key: String,
+    /// First location of every fingerprint
+    pub fingerprints: BTreeMap<u64, Location>,
+}
+
+impl Submission {
+    /// Fingerprints all files inside `dir` that match `glob`, except those in `ignored`.
+    pub fn load(
+        key: &str,
+        dir: &Path,
+        glob: &Regex,
+        ignored: &BTreeSet<u64>,
+    ) -> anyhow::Result<Self> {
+        let mut files = Vec::new();
+        walk(dir, Path::new(""), &mut files)?;
+        files.sort();
+
+        let mut fingerprints = BTreeMap::new();
+        for file in files {
+            let path = file.to_string_lossy().replace('\\', "/");
+            if !glob.is_match(&path) {
+                continue;
+            }
+
+            // binary files are skipped
+            let source = match fs::read_to_string(dir.join(&file)) {
+                Ok(source) => source,
+                Err(_) => continue,
+            };
+            for fingerprint in self::fingerprints(&tokenize(&source)) {
+                if !ignored.contains(&fingerprint.hash) {
+                    fingerprints
+                        .entry(fingerprint.hash)
+                        .or_insert_with(|| Location {
+                            file: file.clone(),
+                            lines: fingerprint.lines,
+                        });
+                }
+            }
+        }
+
+        Ok(Submission {
+            key: key.to_string(),
+            fingerprints,
+        })
+    }
+}
+
+fn walk(dir: &Path, relative: &Path, files: &mut Vec<PathBuf>) -> anyhow::Result<()> {
+    for entry in fs::read_dir(dir.join(relative))? {
+        let entry = entry?;
+        if entry.file_name() == ".git" {
+            continue;
+        }
+
+        let path = relative.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            walk(dir, &path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+/// Two submissions with shared fingerprints.
+#[derive(Debug)]
+pub struct Pair {
+    /// Key of the first project
+    pub first: String,
+    /// Key of the second project
+    pub second: String,
+    /// Number of fingerprints both submissions contain
+    pub shared: usize,
+    /// Share of the fingerprints of the first submission that also occur in the second
+    pub first_similarity: f64,
+    /// Share of the fingerprints of the second submission that also occur in the first
+    pub second_similarity: f64,
+    /// Blocks of shared code, in the order of the first submission
+    pub matches: Vec<Match>,
+}
+
+impl Pair {
+    /// The larger of both similarities, by which pairs are sorted.
+    pub fn similarity(&self) -> f64 {
+        self.first_similarity.max(self.second_similarity)
+    }
+}
+
+/// Line ranges of a block of code found in both submissions.
+#[derive(Debug, PartialEq)]
+pub struct Match {
+    /// The block in the first submission
+    pub first: Location,
+    /// The block in the second submission
+    pub second: Location,
+}
+
+/// Compares every pair of submissions, most similar pairs first.
+pub fn compare(submissions: &[Submission]) -> Vec<Pair> {
+    let mut pairs = Vec::new();
+    for (i, first) in submissions.iter().enumerate() {
+        for second in &submissions[i + 1..] {
+            let shared: Vec<(&Location, &Location)> = first
+                .fingerprints
+                .iter()
+                .filter_map(|(hash, location)| Some((location, second.fingerprints.get(hash)?)))
+                .collect();
+            if shared.is_empty() {
+                continue;
+            }
+
+            let share = |submission: &Submission| {
+                shared.len() as f64 / submission.fingerprints.len() as f64
+            };
+            pairs.push(Pair {
+                first: first.key.clone(),
+                second: second.key.clone(),
+                shared: shared.len(),
+                first_similarity: share(first),
+                second_similarity: share(second),
+                matches: merge(shared),
+            });
+        }
+    }
+
+    pairs.sort_by(|a, b| b.similarity().total_cmp(&a.similarity()));
+    pairs
+}
+
+/// Joins the locations of shared fingerprints into blocks of overlapping or adjacent lines.
+fn merge(mut shared: Vec<(&Location, &Location)>) -> Vec<Match> {
+    shared.sort_by_key(|(first, second)| (&first.file, &second.file, first.lines, second.lines));
+
+    let touches = |a: (usize, usize), b: (usize, usize)| b.0 <= a.1 + 1 && a.0 <= b.1 + 1;
+    let mut matches: Vec<Match> = Vec::new();
+    for (first, second) in shared {
+        match matches.last_mut() {
+            Some(last)
+                if last.first.file == first.file
+                    && last.second.file == second.file
+                    && touches(last.first.lines, first.lines)
+                    && touches(last.second.lines, second.lines) =>
+            {
+                last.first.lines.1 = last.first.lines.1.max(first.lines.1);
+                last.second.lines.0 = last.second.lines.0.min(second.lines.0);
+                last.second.lines.1 = last.second.lines.1.max(second.lines.1);
+            }
+            _ => matches.push(Match {
+                first: first.clone(),
+                second: second.clone(),
+            }),
+        }
+    }
+
+    matches
+}
+
+/// The most similar pairs of projects.
+#[derive(Debug)]
+pub struct Report {
+    /// The glob the compared files matched
+    pub glob: String,
+    /// Pairs with shared code, most similar first
+    pub pairs: Vec<Pair>,
+    /// Keys of projects that are not cloned
+    pub missing: Vec<String>,
+}
+
+impl Report {
+    /// A summary table followed by the matches of every pair.
+    pub fn to_markdown(&self) -> String {
+        let mut markdown = format!("# Similarity of `{}`\n\n", self.glob);
+        if !self.missing.is_empty() {
+            markdown.push_str(&format!("Not cloned: {}\n\n", self.missing.join(", ")));
+        }
+        if self.pairs.is_empty() {
+            markdown.push_str("No similar projects found.\n");
+            return markdown;
+        }
+
+        markdown.push_str("| Projects | Similarity | Shared fingerprints |\n| --- | --- | --- |\n");
+        for pair in &self.pairs {
+            markdown.push_str(&format!(
+                "| {} / {} | {:.0}% / {:.0}% | {} |\n",
+                pair.first,
+                pair.second,
+                pair.first_similarity * 100.0,
+                pair.second_similarity * 100.0,
+                pair.shared
+            ));
+        }
+
+        for pair in &self.pairs {
+            markdown.push_str(&format!(
+                "\n## {} / {}\n\n| {} | {} |\n| --- | --- |\n",
+                pair.first, pair.second, pair.first, pair.second
+            ));
+            for m in &pair.matches {
+                markdown.push_str(&format!(
+                    "| `{}` | `{}` |\n",
+                    location(&m.first),
+                    location(&m.second)
+                ));
+            }
+        }
+
+        markdown
+    }
+
+    /// The same as [`Report::to_markdown`] as a standalone html page.
+    pub fn to_html(&self) -> String {
+        let mut html = format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+             <title>Similarity of {glob}</title>\n</head>\n<body>\n\
+             <h1>Similarity of <code>{glob}</code></h1>\n",
+            glob = escape(&self.glob)
+        );
+        if !self.missing.is_empty() {
+            html.push_str(&format!(
+                "<p>Not cloned: {}</p>\n",
+                escape(&self.missing.join(", "))
+            ));
+        }
+        if self.pairs.is_empty() {
+            html.push_str("<p>No similar projects found.</p>\n");
+        } else {
+            html.push_str(
+                "<table>\n<tr><th>Projects</th><th>Similarity</th>\
+                 <th>Shared fingerprints</th></tr>\n",
+            );
+            for (i, pair) in self.pairs.iter().enumerate() {
+                html.push_str(&format!(
+                    "<tr><td><a href=\"#pair-{}\">{} / {}</a></td>\
+                     <td>{:.0}% / {:.0}%</td><td>{}</td></tr>\n",
+                    i,
+                    escape(&pair.first),
+                    escape(&pair.second),
+                    pair.first_similarity * 100.0,
+                    pair.second_similarity * 100.0,
+                    pair.shared
+                ));
+            }
+            html.push_str("</table>\n");
+
+            for (i, pair) in self.pairs.iter().enumerate() {
+                html.push_str(&format!(
+                    "<h2 id=\"pair-{}\">{first} / {second}</h2>\n<table>\n\
+                     <tr><th>{first}</th><th>{second}</th></tr>\n",
+                    i,
+                    first = escape(&pair.first),
+                    second = escape(&pair.second)
+                ));
+                for m in &pair.matches {
+                    html.push_str(&format!(
+                        "<tr><td><code>{}</code></td><td><code>{}</code></td></tr>\n",
+                        escape(&location(&m.first)),
+                        escape(&location(&m.second))
+                    ));
+                }
+                html.push_str("</table>\n");
+            }
+        }
+        html.push_str("</body>\n</html>\n");
+
+        html
+    }
+}
+
+fn location(location: &Location) -> String {
+    format!(
+        "{}:{}-{}",
+        location.file.display(),
+        location.lines.0,
+        location.lines.1
+    )
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glob_matches_relative_paths() {
+        let rust = glob("src/**/*.rs");
+        assert!(rust.is_match("src/main.rs"));
+        assert!(rust.is_match("src/json/mod.rs"));
+        assert!(!rust.is_match("tests/main.rs"));
+        assert!(!rust.is_match("src/main.rs.orig"));
+
+        let c = glob("*.{c,h}");
+        assert!(c.is_match("list.h"));
+        assert!(!c.is_match("lib/list.c"));
+    }
+
+    #[test]
+    fn tokenize_ignores_names_comments_and_layout() {
+        let original = "fn add(a: u32, b: u32) -> u32 {\n    a + b // sum\n}";
+        let renamed = "/* renamed */ fn plus(x: u32,\n y: u32) -> u32 { x + y }";
+
+        let text = |source| {
+            tokenize(source)
+                .into_iter()
+                .map(|token| token.text)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(text(original), text(renamed));
+        assert_eq!(tokenize(original)[15].line, 2);
+    }
+
+    #[test]
+    fn compare_finds_copies_but_ignores_root_code() {
+        let directory = tempfile::tempdir().unwrap();
+        let root = "fn main() {\n    let input = read();\n    println!(\"{}\", solve(input));\n}\n";
+        let solution = "fn solve(numbers: Vec<u32>) -> u32 {\n    let mut total = 0;\n    \
+                        for n in numbers {\n        if n % 2 == 0 {\n            total += n * n;\n        \
+                        }\n    }\n    total\n}\n";
+        let copy = solution
+            .replace("total", "sum")
+            .replace("numbers", "values");
+        let other = "fn solve(v: Vec<u32>) -> u32 {\n    v.iter().filter(|n| *n % 2 == 0).map(|n| n * n).sum()\n}\n";
+
+        let write = |key: &str, code: &str| {
+            let src = directory.path().join(key).join("src");
+            fs::create_dir_all(&src).unwrap();
+            fs::write(src.join("main.rs"), format!("{}\n{}", root, code)).unwrap();
+            fs::write(src.join("notes.txt"), code).unwrap();
+        };
+        write("root", "");
+        write("team-a", solution);
+        write("team-b", &copy);
+        write("team-c", other);
+
+        let glob = glob("src/**/*.rs");
+        let root = Submission::load(
+            "root",
+            &directory.path().join("root"),
+            &glob,
+            &BTreeSet::new(),
+        )
+        .unwrap();
+        let ignored = root.fingerprints.keys().copied().collect();
+        let submissions: Vec<Submission> = ["team-a", "team-b", "team-c"]
+            .iter()
+            .map(|key| Submission::load(key, &directory.path().join(key), &glob, &ignored).unwrap())
+            .collect();
+
+        let pairs = compare(&submissions);
+
+        assert_eq!(
+            (pairs[0].first.as_str(), pairs[0].second.as_str()),
+            ("team-a", "team-b")
+        );
+        assert_eq!(pairs[0].similarity(), 1.0);
+        assert_eq!(pairs[0].matches.len(), 1);
+        assert_eq!(
+            pairs[0].matches[0].first.file,
+            Path::new("src").join("main.rs")
+        );
+        // without the root, the match would start at the first line
+        assert!(pairs[0].matches[0].first.lines.0 > 1);
+        assert!(pairs[1..].iter().all(|pair| pair.similarity() < 0.5));
+    }
+}
@@ -0,0 +1,112 @@
+use crate::jobs::Outcome;
+use crate::{files, git};
+use chrono::{DateTime, FixedOffset};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// The commit every project was checked out at for a given point in time.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Snapshot {
+    /// The point in time, e.g. the deadline
+    pub before: DateTime<FixedOffset>,
+    /// Sha of the checked out commit by project key
+    pub commits: BTreeMap<String, String>,
+    /// Projects that could not be checked out, with the error
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub errors: BTreeMap<String, String>,
+}
+
+impl Snapshot {
+    /// Records the outcomes of [`checkout_before`], so that failed projects aren't silently
+    /// missing.
+    pub fn new(before: DateTime<FixedOffset>, outcomes: Vec<Outcome<String>>) -> Self {
+        let mut snapshot = Snapshot {
+            before,
+            commits: BTreeMap::new(),
+            errors: BTreeMap::new(),
+        };
+        for outcome in outcomes {
+            match outcome.result {
+                Ok(sha) => snapshot.commits.insert(outcome.key, sha),
+                Err(e) => snapshot.errors.insert(outcome.key, e.to_string()),
+            };
+        }
+        snapshot
+    }
+
+    /// Writes the snapshot to `path`, e.g. `snapshot.yml`.
+    pub fn save(&self, path: &Path, dry_run: bool) -> anyhow::Result<()> {
+        files::write(path, &serde_yaml::to_string(self)?, dry_run)
+    }
+}
+
+/// Checks out the last commit on the default branch before `before` and returns its sha.
+///
+/// The default branch is `origin/HEAD`, which `git clone` sets up. Clones without it, e.g.
+/// created with `git init` and `git remote add`, need `git remote set-head origin --auto`
+/// first.
+pub async fn checkout_before(
+    dir: &Path,
+    before: &DateTime<FixedOffset>,
+    dry_run: bool,
+) -> anyhow::Result<String> {
+    git::run(dir, &["fetch", "origin"], dry_run).await?;
+    let branch = git::default_branch(dir).await?;
+    let sha = git::last_commit_before(dir, &branch, before).await?;
+    git::run(dir, &["checkout", "--detach", &sha], dry_run).await?;
+    Ok(sha)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::test_utils::{git, git_with_env};
+    use crate::jobs::Runner;
+    use std::fs;
+    use tokio::time::Duration;
+
+    #[tokio::test]
+    async fn checkout_before_records_commits_and_errors() {
+        let root = tempfile::tempdir().unwrap();
+        let upstream = root.path().join("upstream");
+        fs::create_dir(&upstream).unwrap();
+        git(&upstream, &["init", "-b", "master"]);
+        for (sheet, date) in [
+            (1, "2020-11-30T12:00:00+01:00"),
+            (2, "2020-12-01T12:00:00+01:00"),
+        ]
+        .iter()
+        {
+            fs::write(upstream.join("README.md"), format!("sheet {}", sheet)).unwrap();
+            git(&upstream, &["add", "README.md"]);
+            git_with_env(
+                &upstream,
+                &["commit", "-m", &format!("sheet {}", sheet)],
+                &[("GIT_AUTHOR_DATE", date), ("GIT_COMMITTER_DATE", date)],
+            );
+        }
+        git(root.path(), &["clone", "upstream", "team-a"]);
+        git(root.path(), &["init", "team-b"]);
+
+        let before = DateTime::parse_from_rfc3339("2020-11-30T23:59:00+01:00").unwrap();
+        let runner = Runner::new(2, Duration::from_millis(0));
+        let outcomes = runner
+            .run(
+                vec![("team-a".to_string(), ()), ("team-b".to_string(), ())],
+                |key, _| {
+                    let dir = root.path().join(key);
+                    async move { checkout_before(&dir, &before, false).await }
+                },
+            )
+            .await;
+        let snapshot = Snapshot::new(before, outcomes);
+
+        let readme = fs::read_to_string(root.path().join("team-a/README.md")).unwrap();
+        assert_eq!(readme, "sheet 1");
+        assert_eq!(snapshot.commits.keys().collect::<Vec<_>>(), vec!["team-a"]);
+        // team-b has no `origin`
+        assert_eq!(snapshot.errors.keys().collect::<Vec<_>>(), vec!["team-b"]);
+    }
+}
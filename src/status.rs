@@ -0,0 +1,224 @@
+use crate::csv;
+use crate::git;
+use crate::jobs::Outcome;
+use chrono::{DateTime, FixedOffset};
+use serde::Serialize;
+use std::fmt;
+use std::path::Path;
+
+/// State of the local clone of one project.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Status {
+    /// Whether the clone exists, all other fields are empty otherwise
+    pub cloned: bool,
+    /// Checked out branch, `None` if the head is detached
+    pub branch: Option<String>,
+    /// Sha of the checked out commit
+    pub sha: Option<String>,
+    /// Whether there are uncommitted or untracked changes
+    pub dirty: bool,
+    /// Commits not pushed to the upstream branch, `None` without upstream
+    pub ahead: Option<usize>,
+    /// Commits not pulled from the upstream branch, `None` without upstream
+    pub behind: Option<usize>,
+    /// The checked out commit, `None` if there is none yet
+    pub last_commit: Option<LastCommit>,
+    /// Whether the feedback file exists, `None` if no feedback was asked for
+    pub feedback: Option<bool>,
+}
+
+/// Author and date of the checked out commit.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct LastCommit {
+    /// Author date
+    pub date: DateTime<FixedOffset>,
+    /// Name of the author
+    pub author: String,
+}
+
+/// Inspects the clone in `dir` and whether the file `feedback` exists, if given.
+pub async fn status(dir: &Path, feedback: Option<&Path>) -> anyhow::Result<Status> {
+    let feedback = feedback.map(Path::exists);
+    if !dir.exists() {
+        return Ok(Status {
+            feedback,
+            ..Status::default()
+        });
+    }
+
+    let tree = git::working_tree(dir).await?;
+    let last_commit = match tree.sha {
+        Some(_) => {
+            let commit = git::last_commit(dir).await?;
+            Some(LastCommit {
+                date: commit.date,
+                author: commit.author_name,
+            })
+        }
+        None => None,
+    };
+
+    Ok(Status {
+        cloned: true,
+        branch: tree.branch,
+        sha: tree.sha,
+        dirty: tree.dirty,
+        ahead: tree.ahead_behind.map(|(ahead, _)| ahead),
+        behind: tree.ahead_behind.map(|(_, behind)| behind),
+        last_commit,
+        feedback,
+    })
+}
+
+/// A status with the key of its project, or the error that occurred while inspecting it.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Row<'a> {
+    /// Key of the project
+    pub key: &'a str,
+    /// The status, `None` if inspecting the clone failed
+    #[serde(flatten)]
+    pub status: Option<&'a Status>,
+    /// Why inspecting the clone failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// One row per project, e.g. for `--format json`.
+pub fn rows(outcomes: &[Outcome<Status>]) -> Vec<Row<'_>> {
+    outcomes
+        .iter()
+        .map(|outcome| Row {
+            key: &outcome.key,
+            status: outcome.result.as_ref().ok(),
+            error: outcome.result.as_ref().err().map(|e| e.to_string()),
+        })
+        .collect()
+}
+
+/// One line per project with a column per field of [`Status`] and `Error`.
+pub fn to_csv(outcomes: &[Outcome<Status>]) -> String {
+    let header = [
+        "Key",
+        "Cloned",
+        "Branch",
+        "Sha",
+        "Dirty",
+        "Ahead",
+        "Behind",
+        "LastCommitDate",
+        "LastCommitAuthor",
+        "Feedback",
+        "Error",
+    ];
+    let mut csv = csv::row(
+        &header
+            .iter()
+            .map(|cell| cell.to_string())
+            .collect::<Vec<_>>(),
+    );
+
+    let optional = |value: Option<String>| value.unwrap_or_default();
+    for row in rows(outcomes) {
+        let cells = match row.status {
+            Some(status) => vec![
+                row.key.to_string(),
+                status.cloned.to_string(),
+                optional(status.branch.clone()),
+                optional(status.sha.clone()),
+                status.dirty.to_string(),
+                optional(status.ahead.map(|ahead| ahead.to_string())),
+                optional(status.behind.map(|behind| behind.to_string())),
+                optional(status.last_commit.as_ref().map(|c| c.date.to_rfc3339())),
+                optional(status.last_commit.as_ref().map(|c| c.author.clone())),
+                optional(status.feedback.map(|feedback| feedback.to_string())),
+                String::new(),
+            ],
+            None => {
+                let mut cells = vec![row.key.to_string()];
+                cells.extend(std::iter::repeat_n(String::new(), header.len() - 2));
+                cells.push(optional(row.error));
+                cells
+            }
+        };
+        csv.push_str(&csv::row(&cells));
+    }
+
+    csv
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        if !self.cloned {
+            parts.push("not cloned".to_string());
+        } else {
+            parts.push(match (&self.branch, &self.sha) {
+                (Some(branch), _) => branch.clone(),
+                (None, Some(sha)) => format!("detached at {}", &sha[..sha.len().min(8)]),
+                (None, None) => "no commits".into(),
+            });
+            if self.dirty {
+                parts.push("dirty".into());
+            }
+            match (self.ahead, self.behind) {
+                (Some(0), Some(0)) => parts.push("up to date".into()),
+                (Some(ahead), Some(behind)) => {
+                    parts.push(format!("{} ahead, {} behind", ahead, behind))
+                }
+                _ => parts.push("no upstream".into()),
+            }
+            if let Some(commit) = &self.last_commit {
+                parts.push(format!(
+                    "last commit {} by {}",
+                    commit.date.to_rfc3339(),
+                    commit.author
+                ));
+            }
+        }
+        match self.feedback {
+            Some(true) => parts.push("feedback exists".into()),
+            Some(false) => parts.push("feedback missing".into()),
+            None => {}
+        }
+
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::git::test_utils::{commit, git};
+    use std::fs;
+
+    #[tokio::test]
+    async fn status_reports_branch_changes_and_feedback() {
+        let root = tempfile::tempdir().unwrap();
+        let upstream = root.path().join("upstream");
+        fs::create_dir(&upstream).unwrap();
+        git(&upstream, &["init", "-b", "master"]);
+        commit(&upstream, "README.md", "sheet 1");
+        git(root.path(), &["clone", "upstream", "team-a"]);
+
+        let clone = root.path().join("team-a");
+        commit(&clone, "main.rs", "fn main() {}");
+        fs::write(clone.join("notes.txt"), "todo").unwrap();
+        let feedback = root.path().join("team-a.md");
+        fs::write(&feedback, "# Sheet 1").unwrap();
+
+        let status = super::status(&clone, Some(&feedback)).await.unwrap();
+
+        assert_eq!(status.branch.as_deref(), Some("master"));
+        assert!(status.dirty);
+        assert_eq!((status.ahead, status.behind), (Some(1), Some(0)));
+        assert_eq!(status.last_commit.unwrap().author, "Alice");
+        assert_eq!(status.feedback, Some(true));
+
+        let status = super::status(&root.path().join("team-b"), None)
+            .await
+            .unwrap();
+        assert_eq!(status.to_string(), "not cloned");
+    }
+}
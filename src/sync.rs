@@ -0,0 +1,498 @@
+use crate::config::{Group, Manifest, Member, Project};
+use crate::gitlab::GitlabApi;
+use crate::jobs::Runner;
+use crate::json::Fork;
+use crate::Error;
+use regex::Regex;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+/// A difference between `forked.yml` and the forks found on gitlab.
+#[derive(Debug, PartialEq)]
+pub enum Change {
+    /// A new project was found
+    Added {
+        /// Key of the project
+        key: String,
+        /// Id of the project on gitlab
+        id: u32,
+    },
+    /// The project no longer exists on gitlab
+    Removed {
+        /// Key of the project
+        key: String,
+        /// Id of the project on gitlab
+        id: u32,
+    },
+    /// The project still exists, but all its members are excluded
+    Excluded {
+        /// Key of the project
+        key: String,
+        /// Id of the project on gitlab
+        id: u32,
+    },
+    /// The members of the project changed
+    Members {
+        /// Key of the project
+        key: String,
+        /// Usernames of new members
+        added: Vec<String>,
+        /// Usernames of members who left
+        removed: Vec<String>,
+    },
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::Added { key, id } => write!(f, "+ {} (project {})", key, id),
+            Change::Removed { key, id } => {
+                write!(f, "- {} (project {} is no longer a fork)", key, id)
+            }
+            Change::Excluded { key, id } => {
+                write!(f, "! {} (all members of project {} are excluded)", key, id)
+            }
+            Change::Members {
+                key,
+                added,
+                removed,
+            } => {
+                write!(f, "~ {}:", key)?;
+                for username in added {
+                    write!(f, " +{}", username)?;
+                }
+                for username in removed {
+                    write!(f, " -{}", username)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Collects all forks of `project_id` that have members left after excluding `exclude_members`.
+pub async fn discover(
+    api: &dyn GitlabApi,
+    runner: &Runner,
+    project_id: u32,
+    exclude_members: &[String],
+) -> anyhow::Result<BTreeMap<String, Project>> {
+    let forks = api.forks(project_id).await?;
+
+    collect(
+        api,
+        runner,
+        forks
+            .into_iter()
+            .map(|fork| (fork.namespace.path.clone(), fork)),
+        exclude_members,
+    )
+    .await
+}
+
+/// Collects all projects of `group` whose name matches its filter and that have members left
+/// after excluding `exclude_members`.
+///
+/// Projects are keyed by their path relative to the group, e.g. `team-a` or `ws20-team-a`.
+pub async fn discover_group(
+    api: &dyn GitlabApi,
+    runner: &Runner,
+    group: &Group,
+    exclude_members: &[String],
+) -> anyhow::Result<BTreeMap<String, Project>> {
+    let filter = match &group.filter {
+        Some(filter) => Some(Regex::new(filter).map_err(|e| Error::InvalidFilter {
+            filter: filter.clone(),
+            message: e.to_string(),
+        })?),
+        None => None,
+    };
+
+    let projects = api
+        .group_projects(&group.path, group.include_subgroups)
+        .await?;
+    let prefix = format!("{}/", group.path);
+
+    collect(
+        api,
+        runner,
+        projects
+            .into_iter()
+            .filter(|project| {
+                let name = project.name.as_deref().or(project.path.as_deref());
+                match (&filter, name) {
+                    (Some(filter), Some(name)) => filter.is_match(name),
+                    (Some(_), None) => false,
+                    (None, _) => true,
+                }
+            })
+            .map(|project| (group_key(&prefix, &project), project)),
+        exclude_members,
+    )
+    .await
+}
+
+fn group_key(prefix: &str, project: &Fork) -> String {
+    project
+        .path_with_namespace
+        .as_deref()
+        .and_then(|path| path.strip_prefix(prefix))
+        .or(project.path.as_deref())
+        .map(|path| path.replace('/', "-"))
+        .unwrap_or_else(|| project.id.to_string())
+}
+
+/// Looks up the members of `projects` and keeps the projects with members left.
+///
+/// Fails if two projects have the same key, instead of silently dropping one of them.
+async fn collect(
+    api: &dyn GitlabApi,
+    runner: &Runner,
+    projects: impl IntoIterator<Item = (String, Fork)>,
+    exclude_members: &[String],
+) -> anyhow::Result<BTreeMap<String, Project>> {
+    let outcomes = runner
+        .run(projects, |_, fork| async {
+            let members = api.members(fork.id).await?;
+            Ok((fork, members))
+        })
+        .await;
+
+    let mut projects: BTreeMap<String, Project> = BTreeMap::new();
+
+    for outcome in outcomes {
+        let (fork, members) = outcome.result?;
+
+        let members: Vec<Member> = members
+            .into_iter()
+            .map(|member| Member {
+                username: member.username,
+                name: member.name,
+            })
+            .filter(|member| !exclude_members.contains(&member.username))
+            .collect();
+
+        if members.is_empty() {
+            continue;
+        }
+
+        if let Some(first) = projects.get(&outcome.key) {
+            return Err(Error::DuplicateKey {
+                key: outcome.key,
+                first: first.id,
+                second: fork.id,
+            }
+            .into());
+        }
+
+        projects.insert(
+            outcome.key,
+            Project {
+                id: fork.id,
+                members,
+                repository: fork.ssh_url_to_repo,
+                web_url: fork.web_url,
+                extra: BTreeMap::new(),
+            },
+        );
+    }
+
+    Ok(projects)
+}
+
+/// Merges the `discovered` projects into `manifest`, matching them by id.
+///
+/// Existing projects are kept as they are, unless `update_members` is set, in which case their
+/// members are replaced. Projects that disappeared are only removed if `prune` is set.
+///
+/// `exclude_members` are removed from the discovered members here, so that projects whose
+/// members are all excluded are reported as [`Change::Excluded`] and kept, instead of being
+/// taken for projects that disappeared. New projects without other members are skipped.
+pub fn merge(
+    manifest: &mut Manifest,
+    discovered: BTreeMap<String, Project>,
+    exclude_members: &[String],
+    update_members: bool,
+    prune: bool,
+) -> Vec<Change> {
+    let mut discovered: BTreeMap<u32, (String, Project)> = discovered
+        .into_iter()
+        .map(|(key, mut project)| {
+            project
+                .members
+                .retain(|member| !exclude_members.contains(&member.username));
+            (project.id, (key, project))
+        })
+        .collect();
+
+    let mut changes = Vec::new();
+    let mut removed = Vec::new();
+
+    for (key, project) in manifest.projects.iter_mut() {
+        match discovered.remove(&project.id) {
+            Some((_, found)) if found.members.is_empty() => {
+                changes.push(Change::Excluded {
+                    key: key.clone(),
+                    id: project.id,
+                });
+            }
+            Some((_, found)) => {
+                if project.web_url.is_none() {
+                    project.web_url = found.web_url.clone();
+                }
+
+                let before = usernames(&project.members);
+                let after = usernames(&found.members);
+
+                if before != after {
+                    changes.push(Change::Members {
+                        key: key.clone(),
+                        added: after.difference(&before).cloned().collect(),
+                        removed: before.difference(&after).cloned().collect(),
+                    });
+
+                    if update_members {
+                        project.members = found.members;
+                    }
+                }
+            }
+            None => {
+                changes.push(Change::Removed {
+                    key: key.clone(),
+                    id: project.id,
+                });
+                removed.push(key.clone());
+            }
+        }
+    }
+
+    if prune {
+        for key in removed {
+            manifest.projects.remove(&key);
+        }
+    }
+
+    let mut added: Vec<(String, Project)> = discovered
+        .into_values()
+        .filter(|(_, project)| !project.members.is_empty())
+        .collect();
+    added.sort_by(|a, b| a.0.cmp(&b.0));
+
+    for (key, project) in added {
+        let key = if manifest.projects.contains_key(&key) {
+            format!("{}-{}", key, project.id)
+        } else {
+            key
+        };
+
+        changes.push(Change::Added {
+            key: key.clone(),
+            id: project.id,
+        });
+        manifest.projects.insert(key, project);
+    }
+
+    changes
+}
+
+fn usernames(members: &[Member]) -> BTreeSet<String> {
+    members
+        .iter()
+        .map(|member| member.username.clone())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gitlab::MockGitlab;
+    use tokio::time::Duration;
+
+    fn manifest(projects: BTreeMap<String, Project>) -> Manifest {
+        Manifest {
+            root_project_id: Some(1),
+            group: None,
+            exclude_members: Vec::new(),
+            aliases: BTreeMap::new(),
+            projects,
+            projects_directory: "projects".into(),
+            templates_directory: "templates".into(),
+            feedbacks_directory: "feedbacks".into(),
+            tests: BTreeMap::new(),
+            retry: None,
+            extra: BTreeMap::new(),
+        }
+    }
+
+    async fn discover_from(api: &MockGitlab) -> BTreeMap<String, Project> {
+        let runner = Runner::new(4, Duration::from_millis(0));
+        discover(api, &runner, 1, &["tutor".to_string()])
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn discover_skips_forks_with_only_excluded_members() {
+        let api = MockGitlab::new()
+            .with_fork(1, 10, "team-a", &["alice", "tutor"])
+            .with_fork(1, 11, "team-b", &["tutor"]);
+
+        let projects = discover_from(&api).await;
+
+        assert_eq!(projects.keys().collect::<Vec<_>>(), vec!["team-a"]);
+        assert_eq!(projects["team-a"].id, 10);
+        assert_eq!(usernames(&projects["team-a"].members).len(), 1);
+        assert_eq!(
+            projects["team-a"].repository,
+            "git@example.com:team-a/exercise.git"
+        );
+    }
+
+    #[tokio::test]
+    async fn discover_group_filters_and_keys_by_relative_path() {
+        let api = MockGitlab::new()
+            .with_group_project("course", 10, "team-a", &["alice"])
+            .with_group_project("course", 11, "handout", &["tutor"])
+            .with_group_project("course/ws20", 12, "team-a", &["bob"])
+            .with_group_project("course/ws20", 13, "slides", &["carol"])
+            .with_group_project("other", 14, "team-z", &["dave"])
+            .with_shared_project("course", 15, "other", "team-a", &["eve"]);
+        let runner = Runner::new(4, Duration::from_millis(0));
+        let group = Group {
+            path: "course".into(),
+            include_subgroups: true,
+            filter: Some("^team-".into()),
+        };
+
+        let projects = discover_group(&api, &runner, &group, &["tutor".to_string()])
+            .await
+            .unwrap();
+
+        assert_eq!(
+            projects.keys().collect::<Vec<_>>(),
+            vec!["team-a", "ws20-team-a"]
+        );
+        assert_eq!(projects["team-a"].id, 10);
+        assert_eq!(projects["ws20-team-a"].id, 12);
+
+        let group = Group {
+            filter: Some("(".into()),
+            ..group
+        };
+        assert!(discover_group(&api, &runner, &group, &[]).await.is_err());
+
+        let api = api.with_group_project("course", 16, "ws20-team-a", &["frank"]);
+        let group = Group {
+            filter: None,
+            ..group
+        };
+        let error = discover_group(&api, &runner, &group, &[])
+            .await
+            .unwrap_err();
+        match error.downcast_ref() {
+            Some(Error::DuplicateKey { key, .. }) => assert_eq!(key, "ws20-team-a"),
+            _ => panic!("unexpected error {}", error),
+        }
+    }
+
+    #[tokio::test]
+    async fn merge_keeps_manual_edits_and_reports_changes() {
+        let mut config = manifest(
+            discover_from(&MockGitlab::new().with_fork(1, 10, "team-a", &["alice"])).await,
+        );
+        config
+            .projects
+            .get_mut("team-a")
+            .unwrap()
+            .extra
+            .insert("Tutor".into(), serde_yaml::Value::String("carol".into()));
+        config.projects.insert(
+            "team-b".into(),
+            Project {
+                id: 11,
+                members: vec![Member {
+                    username: "bob".into(),
+                    name: "Bob".into(),
+                }],
+                repository: "git@example.com:team-b/exercise.git".into(),
+                web_url: None,
+                extra: BTreeMap::new(),
+            },
+        );
+
+        let api = MockGitlab::new()
+            .with_fork(1, 10, "team-a", &["alice", "dave"])
+            .with_fork(1, 12, "team-c", &["erin"]);
+        let changes = merge(&mut config, discover_from(&api).await, &[], false, false);
+
+        assert_eq!(
+            changes,
+            vec![
+                Change::Members {
+                    key: "team-a".into(),
+                    added: vec!["dave".into()],
+                    removed: vec![],
+                },
+                Change::Removed {
+                    key: "team-b".into(),
+                    id: 11,
+                },
+                Change::Added {
+                    key: "team-c".into(),
+                    id: 12,
+                },
+            ]
+        );
+        assert_eq!(config.projects["team-a"].members.len(), 1);
+        assert!(config.projects["team-a"].extra.contains_key("Tutor"));
+        assert!(config.projects.contains_key("team-b"));
+    }
+
+    #[tokio::test]
+    async fn merge_updates_members_and_prunes() {
+        let mut config = manifest(
+            discover_from(
+                &MockGitlab::new()
+                    .with_fork(1, 10, "team-a", &["alice"])
+                    .with_fork(1, 11, "team-b", &["bob"]),
+            )
+            .await,
+        );
+
+        let api = MockGitlab::new().with_fork(1, 10, "team-a", &["alice", "dave"]);
+        merge(&mut config, discover_from(&api).await, &[], true, true);
+
+        assert_eq!(config.projects.keys().collect::<Vec<_>>(), vec!["team-a"]);
+        assert_eq!(config.projects["team-a"].members.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn merge_keeps_projects_whose_members_are_all_excluded() {
+        let api = MockGitlab::new()
+            .with_fork(1, 10, "team-a", &["alice"])
+            .with_fork(1, 11, "team-b", &["bob"]);
+        let mut config = manifest(discover_from(&api).await);
+
+        // bob became a tutor and joined the new team-c as one
+        let api = MockGitlab::new()
+            .with_fork(1, 10, "team-a", &["alice"])
+            .with_fork(1, 11, "team-b", &["bob"])
+            .with_fork(1, 12, "team-c", &["bob"]);
+        let runner = Runner::new(4, Duration::from_millis(0));
+        let discovered = discover(&api, &runner, 1, &[]).await.unwrap();
+        let changes = merge(&mut config, discovered, &["bob".to_string()], true, true);
+
+        assert_eq!(
+            changes,
+            vec![Change::Excluded {
+                key: "team-b".into(),
+                id: 11,
+            }]
+        );
+        assert_eq!(config.projects["team-b"].members.len(), 1);
+        assert_eq!(
+            config.projects.keys().collect::<Vec<_>>(),
+            vec!["team-a", "team-b"]
+        );
+    }
+}
@@ -0,0 +1,501 @@
+use crate::files;
+use crate::sandbox::Sandbox;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::SystemTime;
+use tokio::io::AsyncReadExt;
+use tokio::process::Command;
+use tokio::time::{self, Duration, Instant};
+
+const TESTS_DIRECTORY_NAME: &str = "tests";
+/// Output beyond this many bytes is cut off, so that result files stay readable.
+const MAX_OUTPUT_LENGTH: usize = 64 * 1024;
+
+/// How the tests of an exercise are run, configured per feedback name in `forked.yml`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct TestConfig {
+    /// Shell command run inside every clone, e.g. `cargo test`
+    pub command: String,
+    /// Seconds after which the command is killed
+    #[serde(default = "default_timeout")]
+    pub timeout: u64,
+    /// Path of a JUnit report written by the command, relative to the clone
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub junit: Option<PathBuf>,
+    /// Isolation of the command, see [`Sandbox`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sandbox: Option<Sandbox>,
+}
+
+/// Seconds after which the command is killed, unless configured otherwise.
+pub fn default_timeout() -> u64 {
+    300
+}
+
+/// The result of running the tests of one project, stored in `<feedbacks>/<name>/tests/<key>.yml`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct TestRun {
+    /// The command that was run
+    pub command: String,
+    /// `None` if the command was killed
+    pub exit_code: Option<i32>,
+    /// Whether the command was killed after the timeout
+    pub timed_out: bool,
+    /// Duration of the run in seconds
+    pub duration: f64,
+    /// Standard output, cut off after 64 KiB
+    pub stdout: String,
+    /// Standard error, cut off after 64 KiB
+    pub stderr: String,
+    /// Test cases found in the JUnit report or in TAP output
+    pub cases: Vec<TestCase>,
+    /// Number of passed test cases
+    pub passed: usize,
+    /// Number of failed test cases
+    pub failed: usize,
+    /// Number of skipped test cases
+    pub skipped: usize,
+}
+
+/// A single test of a JUnit report or TAP output.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct TestCase {
+    /// Name of the test, prefixed with its class name in JUnit reports
+    pub name: String,
+    /// Outcome of the test
+    pub status: TestStatus,
+}
+
+/// Outcome of a single test.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum TestStatus {
+    /// The test succeeded
+    Passed,
+    /// The test failed or caused an error
+    Failed,
+    /// The test was skipped or ignored
+    Skipped,
+}
+
+impl TestRun {
+    /// Whether the command finished in time with exit code 0.
+    pub fn succeeded(&self) -> bool {
+        !self.timed_out && self.exit_code == Some(0)
+    }
+}
+
+impl fmt::Display for TestRun {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.timed_out {
+            write!(f, "timed out after {:.0}s", self.duration)?;
+        } else {
+            match self.exit_code {
+                Some(code) => write!(f, "exit code {}", code)?,
+                None => write!(f, "killed")?,
+            }
+        }
+        if !self.cases.is_empty() {
+            write!(
+                f,
+                ", {} passed, {} failed, {} skipped",
+                self.passed, self.failed, self.skipped
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Path to the test results of the project `key` for the feedback `name`.
+pub fn results_path(feedbacks_directory: &Path, name: &str, key: &str) -> PathBuf {
+    feedbacks_directory
+        .join(name)
+        .join(TESTS_DIRECTORY_NAME)
+        .join(format!("{}.yml", key))
+}
+
+/// Loads the test results at `path`, if they exist.
+pub fn load(path: &Path) -> anyhow::Result<Option<TestRun>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    Ok(Some(serde_yaml::from_str(&fs::read_to_string(path)?)?))
+}
+
+/// Runs the tests inside the clone `dir` and stores the result at `results`, or only prints the
+/// command and returns `None` in dry-run mode.
+///
+/// A sandboxed command can't write to `read_only` either, e.g. to the other clones.
+pub async fn run(
+    dir: &Path,
+    config: &TestConfig,
+    read_only: &[PathBuf],
+    results: &Path,
+    dry_run: bool,
+) -> anyhow::Result<Option<TestRun>> {
+    if !dir.exists() {
+        return Err(anyhow::anyhow!("{} is not cloned", dir.display()));
+    }
+    if dry_run {
+        println!(
+            "[dry-run] sh -c {:?} (in {}, timeout {}s)",
+            config.command,
+            dir.display(),
+            config.timeout
+        );
+        if let Some(sandbox) = &config.sandbox {
+            let read_only: Vec<_> = read_only
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect();
+            println!(
+                "[dry-run] sandbox {} with read-only {}",
+                serde_json::to_string(sandbox)?,
+                read_only.join(", ")
+            );
+        }
+        return Ok(None);
+    }
+
+    let mut command = Command::new("sh");
+    command
+        .args(["-c", &config.command])
+        .current_dir(dir)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    // a process group of its own, so that a timeout kills the children of the shell as well
+    #[cfg(unix)]
+    unsafe {
+        command.pre_exec(|| {
+            libc::setpgid(0, 0);
+            Ok(())
+        });
+    }
+    if let Some(sandbox) = &config.sandbox {
+        sandbox.apply(&mut command, dir, read_only)?;
+    }
+
+    let started = SystemTime::now();
+    let start = Instant::now();
+    let mut child = command.spawn()?;
+    let pid = child.id();
+    // the pipes are read here instead of with `wait_with_output`, so that the output written
+    // before a timeout is kept
+    let mut stdout_pipe = child.stdout.take().expect("stdout is piped");
+    let mut stderr_pipe = child.stderr.take().expect("stderr is piped");
+    let (mut stdout, mut stderr) = (Vec::new(), Vec::new());
+    let status = time::timeout(Duration::from_secs(config.timeout), async {
+        let (status, read_stdout, read_stderr) = tokio::join!(
+            &mut child,
+            stdout_pipe.read_to_end(&mut stdout),
+            stderr_pipe.read_to_end(&mut stderr)
+        );
+        read_stdout?;
+        read_stderr?;
+        status
+    })
+    .await;
+    let duration = start.elapsed().as_secs_f64();
+
+    let (exit_code, timed_out) = match status {
+        Ok(status) => (status?.code(), false),
+        Err(_) => {
+            #[cfg(unix)]
+            unsafe {
+                libc::kill(-(pid as libc::pid_t), libc::SIGKILL);
+            }
+            #[cfg(not(unix))]
+            {
+                let _ = pid;
+                let _ = child.kill();
+            }
+            // the killed processes close the pipes, unless one escaped the process group
+            let rest = async {
+                tokio::join!(
+                    stdout_pipe.read_to_end(&mut stdout),
+                    stderr_pipe.read_to_end(&mut stderr)
+                )
+            };
+            let _ = time::timeout(Duration::from_secs(1), rest).await;
+            (None, true)
+        }
+    };
+    let (stdout, stderr) = (truncate(&stdout), truncate(&stderr));
+
+    let mut cases = match &config.junit {
+        Some(report) if written_since(&dir.join(report), started) => {
+            parse_junit(&fs::read_to_string(dir.join(report))?)
+        }
+        Some(report) if dir.join(report).exists() => {
+            log::warn!(
+                "ignoring {}, which is older than the test run",
+                dir.join(report).display()
+            );
+            Vec::new()
+        }
+        _ => Vec::new(),
+    };
+    if cases.is_empty() {
+        cases = parse_tap(&stdout);
+    }
+
+    let count = |status| cases.iter().filter(|case| case.status == status).count();
+    let run = TestRun {
+        command: config.command.clone(),
+        exit_code,
+        timed_out,
+        duration,
+        stdout,
+        stderr,
+        passed: count(TestStatus::Passed),
+        failed: count(TestStatus::Failed),
+        skipped: count(TestStatus::Skipped),
+        cases,
+    };
+
+    if let Some(parent) = results.parent() {
+        files::create_dir_all(parent, dry_run)?;
+    }
+    files::write(results, &serde_yaml::to_string(&run)?, dry_run)?;
+
+    Ok(Some(run))
+}
+
+fn truncate(output: &[u8]) -> String {
+    let output = String::from_utf8_lossy(output);
+    if output.len() <= MAX_OUTPUT_LENGTH {
+        return output.into_owned();
+    }
+
+    let mut end = MAX_OUTPUT_LENGTH;
+    while !output.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}\n[output truncated]", &output[..end])
+}
+
+/// Whether `report` was written since `started`, so that the report of an earlier run, e.g. one
+/// that was committed, isn't taken for the result of this one.
+fn written_since(report: &Path, started: SystemTime) -> bool {
+    // some file systems store the modification time in whole seconds, or even two
+    let started = started - Duration::from_secs(2);
+    fs::metadata(report)
+        .and_then(|metadata| metadata.modified())
+        .is_ok_and(|modified| modified >= started)
+}
+
+/// Extracts the test cases of a JUnit XML report.
+pub fn parse_junit(report: &str) -> Vec<TestCase> {
+    let testcase =
+        Regex::new(r#"(?s)<testcase\b([^>]*?)(?:/>|>(.*?)</testcase>)"#).expect("valid regex");
+    let attribute = |attributes: &str, name: &str| {
+        Regex::new(&format!(r#"\b{}="([^"]*)""#, name))
+            .expect("valid regex")
+            .captures(attributes)
+            .map(|captures| captures[1].to_string())
+    };
+
+    testcase
+        .captures_iter(report)
+        .map(|captures| {
+            let attributes = &captures[1];
+            let body = captures.get(2).map_or("", |body| body.as_str());
+
+            let name = attribute(attributes, "name").unwrap_or_default();
+            let name = match attribute(attributes, "classname") {
+                Some(class) if !class.is_empty() => format!("{}::{}", class, name),
+                _ => name,
+            };
+            let status = if body.contains("<failure") || body.contains("<error") {
+                TestStatus::Failed
+            } else if body.contains("<skipped") {
+                TestStatus::Skipped
+            } else {
+                TestStatus::Passed
+            };
+
+            TestCase { name, status }
+        })
+        .collect()
+}
+
+/// Extracts the test cases of TAP output, e.g. `ok 1 - adds numbers` or `not ok 2 - divides`.
+pub fn parse_tap(output: &str) -> Vec<TestCase> {
+    let line = Regex::new(r"^(not )?ok\b(?:\s+\d+)?(?:\s*-)?\s*([^#]*)(?:#\s*(\w+))?")
+        .expect("valid regex");
+
+    output
+        .lines()
+        .filter_map(|text| line.captures(text.trim_start()))
+        .map(|captures| {
+            let directive = captures
+                .get(3)
+                .map(|directive| directive.as_str().to_uppercase());
+            let status = match directive.as_deref() {
+                Some("SKIP") => TestStatus::Skipped,
+                // failing tests marked as TODO don't count
+                Some("TODO") => TestStatus::Skipped,
+                _ if captures.get(1).is_some() => TestStatus::Failed,
+                _ => TestStatus::Passed,
+            };
+
+            TestCase {
+                name: captures[2].trim().to_string(),
+                status,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_junit_reads_status_of_every_case() {
+        let report = r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuites>
+  <testsuite name="calc" tests="3">
+    <testcase classname="calc" name="adds" time="0.01"/>
+    <testcase classname="calc" name="divides">
+      <failure message="division by zero">panicked</failure>
+    </testcase>
+    <testcase name="later"><skipped/></testcase>
+  </testsuite>
+</testsuites>"#;
+
+        assert_eq!(
+            parse_junit(report),
+            vec![
+                TestCase {
+                    name: "calc::adds".into(),
+                    status: TestStatus::Passed,
+                },
+                TestCase {
+                    name: "calc::divides".into(),
+                    status: TestStatus::Failed,
+                },
+                TestCase {
+                    name: "later".into(),
+                    status: TestStatus::Skipped,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_tap_reads_status_and_directives() {
+        let output = "1..3\nok 1 - adds\nnot ok 2 - divides\nok 3 later # SKIP not ready\n";
+
+        let cases = parse_tap(output);
+
+        assert_eq!(
+            cases
+                .iter()
+                .map(|case| (case.name.as_str(), case.status))
+                .collect::<Vec<_>>(),
+            vec![
+                ("adds", TestStatus::Passed),
+                ("divides", TestStatus::Failed),
+                ("later", TestStatus::Skipped),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn run_stores_output_and_enforces_timeout() {
+        let directory = tempfile::tempdir().unwrap();
+        let results = directory.path().join("tests").join("team-a.yml");
+        let config = |command: &str, timeout| TestConfig {
+            command: command.into(),
+            timeout,
+            junit: None,
+            sandbox: None,
+        };
+
+        let run = super::run(
+            directory.path(),
+            &config("echo 'ok 1 - works'; echo oops >&2; exit 3", 10),
+            &[],
+            &results,
+            false,
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        assert_eq!(run.exit_code, Some(3));
+        assert_eq!(run.stderr, "oops\n");
+        assert_eq!(run.passed, 1);
+        assert_eq!(load(&results).unwrap().unwrap().stdout, "ok 1 - works\n");
+
+        let run = super::run(
+            directory.path(),
+            &config("echo started; sleep 5", 1),
+            &[],
+            &results,
+            false,
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        assert!(run.timed_out);
+        assert!(!run.succeeded());
+        assert_eq!(run.stdout, "started\n");
+        fs::remove_file(&results).unwrap();
+        let run = super::run(
+            directory.path(),
+            &config("touch ran", 10),
+            &[],
+            &results,
+            true,
+        )
+        .await
+        .unwrap();
+        assert!(run.is_none());
+        assert!(!directory.path().join("ran").exists());
+        assert!(!results.exists());
+    }
+
+    #[tokio::test]
+    async fn run_ignores_junit_reports_of_earlier_runs() {
+        let directory = tempfile::tempdir().unwrap();
+        let results = directory.path().join("tests").join("team-a.yml");
+        let report = directory.path().join("junit.xml");
+        fs::write(&report, r#"<testsuite><testcase name="old"/></testsuite>"#).unwrap();
+        let old = SystemTime::now() - Duration::from_secs(60);
+        fs::File::options()
+            .write(true)
+            .open(&report)
+            .unwrap()
+            .set_modified(old)
+            .unwrap();
+        let config = |command: &str| TestConfig {
+            command: command.into(),
+            timeout: 10,
+            junit: Some("junit.xml".into()),
+            sandbox: None,
+        };
+
+        let run = super::run(directory.path(), &config("true"), &[], &results, false)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(run.cases.is_empty());
+
+        let command = r#"echo '<testsuite><testcase name="new"/></testsuite>' > junit.xml"#;
+        let run = super::run(directory.path(), &config(command), &[], &results, false)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(run.cases[0].name, "new");
+    }
+}